@@ -1,6 +1,16 @@
 use glam::{Mat4, Vec3, vec3, const_vec3};
 const UP: Vec3 = const_vec3!([0., 1., 0.,]);
 
+/// Converts a Classic packed angle to degrees.
+pub fn angle_from_byte(angle: u8) -> f32 {
+    angle as f32 * 360. / 256.
+}
+
+/// Converts degrees to a Classic packed angle.
+pub fn angle_to_byte(degrees: f32) -> u8 {
+    (degrees.rem_euclid(360.) * 256. / 360.) as u32 as u8
+}
+
 pub struct Camera {
     fov: f32,
     projection_matrix: Mat4,
@@ -28,6 +38,27 @@ impl Camera {
             self.yaw.to_radians().sin() * self.pitch.to_radians().cos()
         )
     }
+    /// The way the camera looks on screen. The projection is
+    /// left-handed, so this is the opposite of `direction`.
+    pub fn forward(&self) -> Vec3 {
+        -self.direction()
+    }
+    /// Yaw in Classic's convention, in degrees:
+    /// 0 faces -Z and 90 faces +X.
+    pub fn classic_yaw(&self) -> f32 {
+        self.yaw - 90.
+    }
+    /// Pitch in Classic's convention, in
+    /// degrees. Positive looks down.
+    pub fn classic_pitch(&self) -> f32 {
+        self.pitch
+    }
+    /// Points the camera using Classic's yaw and pitch, in degrees.
+    pub fn set_classic_orientation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw + 90.;
+        let pitch = pitch.rem_euclid(360.);
+        self.pitch = if pitch > 180. { pitch - 360. } else { pitch }.clamp(-89., 89.);
+    }
     pub fn right(&self) -> Vec3 {
         let camera_front = self.direction().normalize();
         camera_front.cross(UP).normalize()
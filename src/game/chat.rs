@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+
+use fnv::FnvHashMap;
+use glam::{vec3, Vec3};
+
+use super::render_stages::text::TextSegment;
+
+/// Character which starts a colour code.
+const CODE_PREFIX: char = '&';
+
+/// Chat lines kept for display.
+const MAX_LINES: usize = 10;
+
+/// Colours for `&x` codes. Starts with the sixteen
+/// Classic colours; servers add more with SetTextColor.
+pub struct ColorTable {
+    colors: FnvHashMap<char, Vec3>,
+}
+impl Default for ColorTable {
+    fn default() -> Self {
+        const CLASSIC: [(char, [u8; 3]); 16] = [
+            ('0', [0, 0, 0]), ('1', [0, 0, 191]), ('2', [0, 191, 0]), ('3', [0, 191, 191]),
+            ('4', [191, 0, 0]), ('5', [191, 0, 191]), ('6', [191, 191, 0]), ('7', [191, 191, 191]),
+            ('8', [64, 64, 64]), ('9', [64, 64, 255]), ('a', [64, 255, 64]), ('b', [64, 255, 255]),
+            ('c', [255, 64, 64]), ('d', [255, 64, 255]), ('e', [255, 255, 64]), ('f', [255, 255, 255]),
+        ];
+        let mut table = Self { colors: FnvHashMap::default() };
+        for (code, [r, g, b]) in CLASSIC {
+            table.set(code as u8, r, g, b);
+        }
+        table
+    }
+}
+impl ColorTable {
+    /// Handles SetTextColor. Codes which would clash with
+    /// the prefix or plain text layout are ignored.
+    pub fn set(&mut self, code: u8, red: u8, green: u8, blue: u8) {
+        let code = code as char;
+        if code == CODE_PREFIX || code.is_whitespace() || code.is_control() || !code.is_ascii() {
+            log::warn!("Server tried to define invalid colour code {:?}", code);
+            return;
+        }
+        self.colors.insert(code, vec3(red as f32, green as f32, blue as f32) / 255.);
+    }
+    /// Looks up a code. Letters fall back to their
+    /// lowercase form, so `&A` matches `&a`.
+    pub fn get(&self, code: char) -> Option<Vec3> {
+        self.colors.get(&code).or_else(|| self.colors.get(&code.to_ascii_lowercase())).copied()
+    }
+    /// Splits `text` into segments at each known colour code.
+    /// Text before the first code uses `default`. Unknown codes
+    /// are kept as written.
+    pub fn parse(&self, text: &str, default: Vec3) -> Vec<TextSegment> {
+        let mut segments = Vec::new();
+        let mut current = TextSegment::new(String::new(), default);
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == CODE_PREFIX {
+                if let Some(color) = chars.peek().and_then(|&code| self.get(code)) {
+                    chars.next();
+                    let next = TextSegment::new(String::new(), color);
+                    let finished = std::mem::replace(&mut current, next);
+                    if !finished.text.is_empty() {
+                        segments.push(finished);
+                    }
+                    continue;
+                }
+            }
+            current.text.push(c);
+        }
+        if !current.text.is_empty() {
+            segments.push(current);
+        }
+        segments
+    }
+    /// Returns `text` with its known colour codes removed.
+    pub fn strip(&self, text: &str) -> String {
+        self.parse(text, Vec3::ONE).into_iter().map(|s| s.text).collect()
+    }
+}
+
+/// Recent chat messages and the colours used to show them.
+#[derive(Default)]
+pub struct Chat {
+    lines: VecDeque<String>,
+    pub colors: ColorTable,
+}
+impl Chat {
+    pub fn add_message(&mut self, message: String) {
+        log::info!("Message {}", self.colors.strip(&message));
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(message);
+    }
+    /// Messages from oldest to newest, with colour codes intact.
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+}
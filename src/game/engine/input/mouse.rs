@@ -1,3 +1,6 @@
+use fxhash::FxHashMap;
+use glutin::event::{ElementState, MouseButton};
+
 /// Holds last offset of the mouse
 /// cursor.
 #[derive(Default)]
@@ -8,7 +11,12 @@ pub struct MouseInput {
     pub last_y: f64,
     pub x: f64,
     pub y: f64,
-    pub updated: bool
+    pub updated: bool,
+    /// Button state, and whether the
+    /// change has been seen yet.
+    buttons: FxHashMap<MouseButton, (ElementState, bool)>,
+    /// Wheel movement not yet consumed, in lines.
+    scroll: f32,
 }
 impl MouseInput {
     pub fn reset(&mut self) {
@@ -22,4 +30,38 @@ impl MouseInput {
         self.y += y;
         self.updated = true;
     }
+    /// Processes a mouse button event.
+    pub fn process_button(&mut self, state: ElementState, button: MouseButton) {
+        self.buttons.insert(button, (state, true));
+    }
+    /// Returns true if `button` was pressed
+    /// since the last call.
+    pub fn was_pressed(&mut self, button: MouseButton) -> bool {
+        self.take_change(button, ElementState::Pressed)
+    }
+    /// Returns true if `button` was released
+    /// since the last call.
+    pub fn was_released(&mut self, button: MouseButton) -> bool {
+        self.take_change(button, ElementState::Released)
+    }
+    /// Accumulates mouse wheel movement.
+    pub fn process_scroll(&mut self, lines: f32) {
+        self.scroll += lines;
+    }
+    /// Returns whole lines scrolled since
+    /// the last call. Positive is up.
+    pub fn take_scroll(&mut self) -> i32 {
+        let lines = self.scroll.trunc();
+        self.scroll -= lines;
+        lines as i32
+    }
+    fn take_change(&mut self, button: MouseButton, state: ElementState) -> bool {
+        if let Some(v) = self.buttons.get_mut(&button) {
+            if v.1 && v.0 == state {
+                v.1 = false;
+                return true;
+            }
+        }
+        false
+    }
 }
\ No newline at end of file
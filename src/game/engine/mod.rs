@@ -1,6 +1,6 @@
 use std::{rc::Rc, cell::RefCell, marker::PhantomData, time::Duration};
 
-use glutin::{dpi::Size, event::{Event, WindowEvent, DeviceEvent}};
+use glutin::{dpi::Size, event::{Event, WindowEvent, DeviceEvent, MouseScrollDelta}};
 
 use crate::render::{stage::{RenderStageObj, RenderStage}, RenderEngine, window::GameWindow};
 
@@ -42,6 +42,22 @@ impl<V: 'static> GameEngine<V> {
                         engine.input.keyboard.process_event(input.state, code);
                     }
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::MouseInput { state, button, .. },
+                    ..
+                } => {
+                    engine.input.mouse.process_button(*state, *button);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::MouseWheel { delta, .. },
+                    ..
+                } => {
+                    let lines = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => *y,
+                        MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.,
+                    };
+                    engine.input.mouse.process_scroll(lines);
+                }
                 Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
                     engine.input.mouse.last_offset_x = delta.0;
                     engine.input.mouse.last_offset_y = delta.1;
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use glam::Vec3;
+
+/// How far behind the newest update entities are shown, in
+/// seconds. Two packets at 20 Hz, so there is usually a later
+/// state to move towards.
+const DELAY: f32 = 0.1;
+/// Longest an entity keeps moving past its newest update, in seconds.
+const MAX_EXTRAPOLATION: f32 = 0.1;
+/// Moves further than this, in blocks, are shown as teleports.
+const SNAP_DISTANCE: f32 = 4.;
+/// Most updates kept per entity.
+const MAX_STATES: usize = 16;
+
+/// Where an entity is and where it is looking.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EntityState {
+    /// Feet position, in blocks.
+    pub position: Vec3,
+    /// Classic yaw and pitch, in degrees.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+impl EntityState {
+    pub fn new(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        Self { position, yaw, pitch }
+    }
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            yaw: lerp_angle(self.yaw, other.yaw, t),
+            pitch: lerp_angle(self.pitch, other.pitch, t),
+        }
+    }
+}
+
+/// Interpolates between two angles in degrees the short way round.
+fn lerp_angle(from: f32, to: f32, t: f32) -> f32 {
+    let difference = (to - from + 180.).rem_euclid(360.) - 180.;
+    from + difference * t
+}
+
+/// Buffers the updates received for an entity and smooths
+/// between them, so movement sent at 20 Hz can be shown at
+/// any frame rate.
+pub struct Interpolator {
+    /// Updates with the time they arrived, oldest first. Never empty.
+    states: VecDeque<(f32, EntityState)>,
+    /// Seconds since this interpolator was created.
+    time: f32,
+}
+impl Interpolator {
+    pub fn new(state: EntityState) -> Self {
+        let mut states = VecDeque::with_capacity(MAX_STATES);
+        states.push_back((0., state));
+        Self { states, time: 0. }
+    }
+    /// The newest update, which relative moves build on.
+    pub fn latest(&self) -> EntityState {
+        self.states.back().unwrap().1
+    }
+    /// Adds an update to move towards. Far moves snap instead.
+    pub fn push(&mut self, state: EntityState) {
+        if state.position.distance(self.latest().position) > SNAP_DISTANCE {
+            self.teleport(state);
+            return;
+        }
+        // After a pause, start from where the entity stood rather
+        // than sliding slowly across the whole pause.
+        let (last_time, last) = self.states.back().copied().unwrap();
+        if last_time < self.time - DELAY {
+            self.states.push_back((self.time - DELAY, last));
+        }
+        while self.states.len() >= MAX_STATES {
+            self.states.pop_front();
+        }
+        self.states.push_back((self.time, state));
+    }
+    /// Jumps straight to `state`, forgetting earlier updates.
+    pub fn teleport(&mut self, state: EntityState) {
+        self.states.clear();
+        self.states.push_back((self.time - DELAY, state));
+    }
+    /// Moves every buffered update by `offset`, so the
+    /// entity is shifted without any visible movement.
+    pub fn shift(&mut self, offset: Vec3) {
+        for (_, state) in self.states.iter_mut() {
+            state.position += offset;
+        }
+    }
+    /// Advances the clock by `delta` seconds.
+    pub fn advance(&mut self, delta: f32) {
+        self.time += delta;
+        // Drop updates older than the pair being shown.
+        let shown = self.time - DELAY;
+        while self.states.len() > 2 && self.states[1].0 <= shown {
+            self.states.pop_front();
+        }
+    }
+    /// The state to show now.
+    pub fn current(&self) -> EntityState {
+        let shown = self.time - DELAY;
+        let (first_time, first) = self.states[0];
+        if shown <= first_time || self.states.len() == 1 {
+            return first;
+        }
+        for window in self.states.iter().collect::<Vec<_>>().windows(2) {
+            let (&(t0, s0), &(t1, s1)) = (window[0], window[1]);
+            if shown <= t1 {
+                return s0.lerp(&s1, (shown - t0) / (t1 - t0).max(f32::EPSILON));
+            }
+        }
+        // Past the newest update: keep going the same way for a while.
+        let (t1, s1) = self.states[self.states.len() - 1];
+        let (t0, s0) = self.states[self.states.len() - 2];
+        let beyond = (shown - t1).min(MAX_EXTRAPOLATION);
+        s0.lerp(&s1, 1. + beyond / (t1 - t0).max(f32::EPSILON))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+
+    fn at(x: f32) -> EntityState {
+        EntityState::new(vec3(x, 0., 0.), 0., 0.)
+    }
+
+    #[test]
+    fn holds_still_without_updates() {
+        let mut motion = Interpolator::new(at(1.));
+        motion.advance(1.);
+        assert_eq!(motion.current(), at(1.));
+    }
+
+    #[test]
+    fn interpolates_between_updates() {
+        let mut motion = Interpolator::new(at(0.));
+        motion.advance(0.05);
+        motion.push(at(1.));
+        motion.advance(0.05);
+        motion.push(at(2.));
+        // Shown time is 0.1 - DELAY = 0, then halfway to the second update.
+        assert_eq!(motion.current().position.x, 0.);
+        motion.advance(0.025);
+        assert!((motion.current().position.x - 0.5).abs() < 1e-4);
+        motion.advance(0.05);
+        assert!((motion.current().position.x - 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn extrapolates_for_a_limited_time() {
+        let mut motion = Interpolator::new(at(0.));
+        motion.advance(0.05);
+        motion.push(at(1.));
+        // 0.1 past the update at 1 block per 0.05 seconds.
+        motion.advance(DELAY + 0.1);
+        assert!((motion.current().position.x - 3.).abs() < 1e-4);
+        motion.advance(1.);
+        assert!((motion.current().position.x - 3.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn snaps_on_teleport_and_far_moves() {
+        let mut motion = Interpolator::new(at(0.));
+        motion.advance(0.05);
+        motion.teleport(at(2.));
+        assert_eq!(motion.current(), at(2.));
+        motion.push(at(2. + SNAP_DISTANCE + 1.));
+        assert_eq!(motion.current(), at(3. + SNAP_DISTANCE));
+    }
+
+    #[test]
+    fn starts_moving_after_a_pause() {
+        let mut motion = Interpolator::new(at(0.));
+        motion.advance(5.);
+        motion.push(at(1.));
+        assert_eq!(motion.current(), at(0.));
+        motion.advance(DELAY / 2.);
+        assert!((motion.current().position.x - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn shift_moves_without_interpolating() {
+        let mut motion = Interpolator::new(at(0.));
+        motion.shift(vec3(5., 0., 0.));
+        assert_eq!(motion.current(), at(5.));
+        assert_eq!(motion.latest(), at(5.));
+    }
+
+    #[test]
+    fn turns_the_short_way_round() {
+        assert!((lerp_angle(350., 10., 0.5) - 360.).abs() < 1e-4);
+        assert!((lerp_angle(10., 350., 0.5)).abs() < 1e-4);
+    }
+}
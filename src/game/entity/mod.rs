@@ -0,0 +1,163 @@
+use glam::{Vec3, Vec3Swizzles};
+
+use self::interpolation::{EntityState, Interpolator};
+
+pub mod interpolation;
+pub mod model;
+
+/// Radians limbs swing through per block walked.
+const STRIDE: f32 = 4.;
+/// How quickly limbs start and stop swinging, per second.
+const SWING_RATE: f32 = 6.;
+/// Seconds without moving before limbs come to rest.
+/// Longer than the gap between movement packets.
+const IDLE_TIME: f32 = 0.2;
+
+/// How an ExtEntityTeleport moves its entity.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveMode {
+    AbsoluteInstant,
+    AbsoluteSmooth,
+    RelativeSmooth,
+    /// Relative, without any visible movement.
+    RelativeSeamless,
+}
+impl MoveMode {
+    pub fn is_relative(&self) -> bool {
+        matches!(self, MoveMode::RelativeSmooth | MoveMode::RelativeSeamless)
+    }
+}
+
+/// The flags of an ExtEntityTeleport's behaviour byte.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TeleportBehavior {
+    /// How to move, or `None` to keep the position.
+    pub position: Option<MoveMode>,
+    /// Yaw and pitch are only ever used together.
+    pub yaw: bool,
+    pub pitch: bool,
+    /// Whether to turn smoothly rather than snapping.
+    pub interpolate_orientation: bool,
+}
+impl TeleportBehavior {
+    pub fn from_byte(behavior: u8) -> Self {
+        const USE_POSITION: u8 = 0x01;
+        const USE_ORIENTATION: u8 = 0x10;
+        const INTERPOLATE_ORIENTATION: u8 = 0x20;
+        let mode = match (behavior >> 1) & 0x03 {
+            0 => MoveMode::AbsoluteInstant,
+            1 => MoveMode::AbsoluteSmooth,
+            2 => MoveMode::RelativeSmooth,
+            _ => MoveMode::RelativeSeamless,
+        };
+        Self {
+            position: (behavior & USE_POSITION != 0).then_some(mode),
+            yaw: behavior & USE_ORIENTATION != 0,
+            pitch: behavior & USE_ORIENTATION != 0,
+            interpolate_orientation: behavior & INTERPOLATE_ORIENTATION != 0,
+        }
+    }
+}
+
+/// Another player in the world, as spawned by SpawnPlayer.
+pub struct Entity {
+    pub name: String,
+    /// Feet position as currently shown, in blocks.
+    pub position: Vec3,
+    /// Classic yaw and pitch as currently shown, in degrees.
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Where the walk cycle is, in radians.
+    pub walk_phase: f32,
+    /// How far limbs swing, from 0 standing still to 1 walking.
+    pub swing: f32,
+    /// Updates from the server, smoothed into `position`, `yaw` and `pitch`.
+    motion: Interpolator,
+    /// Position at the last animation update.
+    last_position: Vec3,
+    /// Seconds since the entity last moved.
+    idle: f32,
+}
+impl Entity {
+    pub fn new(name: String, state: EntityState) -> Self {
+        Self {
+            name,
+            position: state.position,
+            yaw: state.yaw,
+            pitch: state.pitch,
+            walk_phase: 0.,
+            swing: 0.,
+            motion: Interpolator::new(state),
+            last_position: state.position,
+            idle: IDLE_TIME,
+        }
+    }
+    /// The newest state sent by the server, which
+    /// may be ahead of what is currently shown.
+    pub fn target(&self) -> EntityState {
+        self.motion.latest()
+    }
+    /// Starts moving smoothly towards `state`.
+    pub fn move_to(&mut self, state: EntityState) {
+        self.motion.push(state);
+    }
+    /// Jumps to `state` without animating limbs.
+    pub fn teleport(&mut self, state: EntityState) {
+        self.motion.teleport(state);
+        self.show(state);
+        self.last_position = state.position;
+    }
+    /// Moves the entity by `offset` blocks without any visible movement.
+    pub fn shift(&mut self, offset: Vec3) {
+        self.motion.shift(offset);
+        self.position += offset;
+        self.last_position += offset;
+    }
+    /// Advances movement and animation by `delta` seconds.
+    pub fn update(&mut self, delta: f32) {
+        self.motion.advance(delta);
+        self.show(self.motion.current());
+        self.animate(delta);
+    }
+    fn show(&mut self, state: EntityState) {
+        self.position = state.position;
+        self.yaw = state.yaw;
+        self.pitch = state.pitch;
+    }
+    /// Advances the walk animation by `delta` seconds,
+    /// based on how far the entity moved since last time.
+    fn animate(&mut self, delta: f32) {
+        let moved = (self.position - self.last_position).xz().length();
+        self.last_position = self.position;
+        self.walk_phase = (self.walk_phase + moved * STRIDE) % std::f32::consts::TAU;
+        self.idle = if moved > 0. { 0. } else { self.idle + delta };
+        let target = if self.idle < IDLE_TIME { 1. } else { 0. };
+        let step = SWING_RATE * delta;
+        self.swing += (target - self.swing).clamp(-step, step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_teleport_behavior() {
+        let behavior = TeleportBehavior::from_byte(0x10);
+        assert_eq!(behavior, TeleportBehavior { position: None, yaw: true, pitch: true, interpolate_orientation: false });
+        let behavior = TeleportBehavior::from_byte(0x30);
+        assert_eq!(behavior, TeleportBehavior { position: None, yaw: true, pitch: true, interpolate_orientation: true });
+        for (byte, mode) in [
+            (0x01, MoveMode::AbsoluteInstant),
+            (0x03, MoveMode::AbsoluteSmooth),
+            (0x05, MoveMode::RelativeSmooth),
+            (0x07, MoveMode::RelativeSeamless),
+        ] {
+            let behavior = TeleportBehavior::from_byte(byte);
+            assert_eq!(behavior.position, Some(mode));
+            assert!(!behavior.yaw && !behavior.pitch && !behavior.interpolate_orientation);
+        }
+        // The mode is ignored without UsePosition.
+        assert_eq!(TeleportBehavior::from_byte(0x06).position, None);
+    }
+}
@@ -0,0 +1,305 @@
+use fnv::FnvHashMap;
+use glam::{vec2, vec3, Mat4, Vec2, Vec3, Vec4};
+
+use crate::game::{mesh::database::BlockMeshDatabase, world::BlockId};
+
+/// Pixels per block in model coordinates.
+const PIXELS: f32 = 16.;
+
+/// What drives a part's animation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PartKind {
+    Head,
+    Body,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+}
+
+/// The texture a model is drawn with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModelTexture {
+    /// The entity's skin, falling back to `char.png`.
+    Skin,
+    /// A fixed texture file, such as `pig.png`.
+    File(&'static str),
+    /// The terrain atlas, used by block models.
+    Terrain,
+}
+
+/// A rigid piece of a model which rotates about `pivot`.
+#[derive(Clone, Debug)]
+pub struct ModelPart {
+    pub kind: PartKind,
+    /// In blocks, relative to the model's feet.
+    pub pivot: Vec3,
+    pub vertices: Vec<Vec4>,
+    /// In texture pixels, see `EntityModel::texture_size`.
+    pub texcoords: Vec<Vec2>,
+}
+impl ModelPart {
+    fn new(kind: PartKind, pivot: [f32; 3]) -> Self {
+        Self {
+            kind,
+            pivot: Vec3::from(pivot) / PIXELS,
+            vertices: Vec::new(),
+            texcoords: Vec::new(),
+        }
+    }
+    /// Adds a box spanning `from`-`to` in pixels, textured
+    /// with the usual skin layout starting at `uv`.
+    fn cuboid(mut self, from: [f32; 3], to: [f32; 3], uv: [f32; 2]) -> Self {
+        let [x1, y1, z1] = from.map(|v| v / PIXELS);
+        let [x2, y2, z2] = to.map(|v| v / PIXELS);
+        let (w, h, d) = (to[0] - from[0], to[1] - from[1], to[2] - from[2]);
+        let [u, v] = uv;
+        // Corners go top left, top right, bottom right, bottom left.
+        let faces = [
+            // Top, bottom.
+            ([vec3(x2, y2, z2), vec3(x1, y2, z2), vec3(x1, y2, z1), vec3(x2, y2, z1)], vec2(u + d, v), vec2(w, d)),
+            ([vec3(x2, y1, z1), vec3(x1, y1, z1), vec3(x1, y1, z2), vec3(x2, y1, z2)], vec2(u + d + w, v), vec2(w, d)),
+            // Right, front, left, back.
+            ([vec3(x2, y2, z2), vec3(x2, y2, z1), vec3(x2, y1, z1), vec3(x2, y1, z2)], vec2(u, v + d), vec2(d, h)),
+            ([vec3(x2, y2, z1), vec3(x1, y2, z1), vec3(x1, y1, z1), vec3(x2, y1, z1)], vec2(u + d, v + d), vec2(w, h)),
+            ([vec3(x1, y2, z1), vec3(x1, y2, z2), vec3(x1, y1, z2), vec3(x1, y1, z1)], vec2(u + d + w, v + d), vec2(d, h)),
+            ([vec3(x1, y2, z2), vec3(x2, y2, z2), vec3(x2, y1, z2), vec3(x1, y1, z2)], vec2(u + d + w + d, v + d), vec2(w, h)),
+        ];
+        for (corners, origin, size) in faces {
+            let uvs = [
+                origin,
+                origin + vec2(size.x, 0.),
+                origin + size,
+                origin + vec2(0., size.y),
+            ];
+            for i in [0, 1, 2, 2, 3, 0] {
+                self.vertices.push(corners[i].extend(1.));
+                self.texcoords.push(uvs[i]);
+            }
+        }
+        self
+    }
+}
+
+/// The shape of an entity, made of animated parts.
+#[derive(Clone, Debug)]
+pub struct EntityModel {
+    pub parts: Vec<ModelPart>,
+    pub texture: ModelTexture,
+    /// Size of the texture `texcoords` are given in.
+    pub texture_size: Vec2,
+    /// Scale applied before any EntityProperty scaling.
+    pub scale: f32,
+    /// Whether arms are held out in front, like a zombie.
+    pub arms_forward: bool,
+}
+impl EntityModel {
+    fn new(texture: ModelTexture, parts: Vec<ModelPart>) -> Self {
+        Self {
+            parts,
+            texture,
+            texture_size: vec2(64., 32.),
+            scale: 1.,
+            arms_forward: false,
+        }
+    }
+    fn scaled(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+    fn humanoid(texture: ModelTexture, limb_width: f32) -> Self {
+        let l = limb_width;
+        Self::new(texture, vec![
+            ModelPart::new(PartKind::Head, [0., 24., 0.])
+                .cuboid([-4., 24., -4.], [4., 32., 4.], [0., 0.]),
+            ModelPart::new(PartKind::Body, [0., 12., 0.])
+                .cuboid([-4., 12., -2.], [4., 24., 2.], [16., 16.]),
+            ModelPart::new(PartKind::RightArm, [4. + l / 2., 22., 0.])
+                .cuboid([4., 12., -l / 2.], [4. + l, 24., l / 2.], [40., 16.]),
+            ModelPart::new(PartKind::LeftArm, [-4. - l / 2., 22., 0.])
+                .cuboid([-4. - l, 12., -l / 2.], [-4., 24., l / 2.], [40., 16.]),
+            ModelPart::new(PartKind::RightLeg, [l / 2., 12., 0.])
+                .cuboid([0., 0., -l / 2.], [l, 12., l / 2.], [0., 16.]),
+            ModelPart::new(PartKind::LeftLeg, [-l / 2., 12., 0.])
+                .cuboid([-l, 0., -l / 2.], [0., 12., l / 2.], [0., 16.]),
+        ])
+    }
+    fn quadruped(texture: ModelTexture, head: ModelPart, body: ModelPart, leg: [f32; 3], leg_uv: [f32; 2], spacing: [f32; 2]) -> Self {
+        let [w, h, d] = leg;
+        let [sx, sz] = spacing;
+        let leg = |kind, x: f32, z: f32| {
+            ModelPart::new(kind, [x, h, z])
+                .cuboid([x - w / 2., 0., z - d / 2.], [x + w / 2., h, z + d / 2.], leg_uv)
+        };
+        Self::new(texture, vec![
+            head,
+            body,
+            leg(PartKind::RightLeg, sx, -sz),
+            leg(PartKind::LeftLeg, -sx, -sz),
+            leg(PartKind::LeftLeg, sx, sz),
+            leg(PartKind::RightLeg, -sx, sz),
+        ])
+    }
+    /// A single block standing on the ground, as used
+    /// by models named after a block ID.
+    fn block(database: &BlockMeshDatabase, id: BlockId) -> Self {
+        // Block meshes are centered and half a block wide.
+        let transform = Mat4::from_translation(vec3(0., 0.5, 0.)) * Mat4::from_scale(Vec3::splat(2.));
+        let (vertices, texcoords) = database.get(id).into_parts();
+        let mut part = ModelPart::new(PartKind::Body, [0., 0., 0.]);
+        part.vertices = vertices.into_iter().map(|v| transform * v).collect();
+        part.texcoords = texcoords;
+        let mut model = Self::new(ModelTexture::Terrain, vec![part]);
+        model.texture_size = vec2(1., 1.);
+        model
+    }
+}
+
+/// Maps model names, as sent by ChangeModel, to models.
+pub struct ModelRegistry {
+    models: FnvHashMap<String, EntityModel>,
+    blocks: BlockMeshDatabase,
+}
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        let mut models = FnvHashMap::default();
+        let humanoid = EntityModel::humanoid(ModelTexture::Skin, 4.);
+        models.insert("giant".to_string(), humanoid.clone().scaled(6.));
+        models.insert("humanoid".to_string(), humanoid);
+        models.insert("skeleton".to_string(), EntityModel::humanoid(ModelTexture::File("skeleton.png"), 2.));
+        let mut zombie = EntityModel::humanoid(ModelTexture::File("zombie.png"), 4.);
+        zombie.arms_forward = true;
+        models.insert("zombie".to_string(), zombie);
+        models.insert("creeper".to_string(), EntityModel::quadruped(
+            ModelTexture::File("creeper.png"),
+            ModelPart::new(PartKind::Head, [0., 18., 0.])
+                .cuboid([-4., 18., -4.], [4., 26., 4.], [0., 0.]),
+            ModelPart::new(PartKind::Body, [0., 6., 0.])
+                .cuboid([-4., 6., -2.], [4., 18., 2.], [16., 16.]),
+            [4., 6., 4.], [0., 16.], [2., 4.],
+        ));
+        models.insert("pig".to_string(), EntityModel::quadruped(
+            ModelTexture::File("pig.png"),
+            ModelPart::new(PartKind::Head, [0., 12., -6.])
+                .cuboid([-4., 8., -14.], [4., 16., -6.], [0., 0.]),
+            ModelPart::new(PartKind::Body, [0., 10., 0.])
+                .cuboid([-5., 6., -8.], [5., 14., 8.], [28., 8.]),
+            [4., 6., 4.], [0., 16.], [3., 7.],
+        ));
+        models.insert("sheep".to_string(), EntityModel::quadruped(
+            ModelTexture::File("sheep.png"),
+            ModelPart::new(PartKind::Head, [0., 18., -8.])
+                .cuboid([-3., 16., -14.], [3., 22., -6.], [0., 0.]),
+            ModelPart::new(PartKind::Body, [0., 15., 0.])
+                .cuboid([-4., 12., -8.], [4., 18., 8.], [28., 8.]),
+            [4., 12., 4.], [0., 16.], [3., 5.],
+        ));
+        models.insert("chicken".to_string(), EntityModel::new(ModelTexture::File("chicken.png"), vec![
+            ModelPart::new(PartKind::Head, [0., 9., -4.])
+                .cuboid([-2., 9., -6.], [2., 15., -3.], [0., 0.])
+                .cuboid([-2., 11., -8.], [2., 13., -6.], [14., 0.])
+                .cuboid([-1., 9., -7.], [1., 11., -5.], [14., 4.]),
+            ModelPart::new(PartKind::Body, [0., 8., 0.])
+                .cuboid([-3., 5., -4.], [3., 11., 4.], [0., 9.]),
+            ModelPart::new(PartKind::RightArm, [3., 11., 0.])
+                .cuboid([3., 7., -3.], [4., 11., 3.], [24., 13.]),
+            ModelPart::new(PartKind::LeftArm, [-3., 11., 0.])
+                .cuboid([-4., 7., -3.], [-3., 11., 3.], [24., 13.]),
+            ModelPart::new(PartKind::RightLeg, [1.5, 5., 1.])
+                .cuboid([0., 0., -0.5], [3., 5., 2.5], [26., 0.]),
+            ModelPart::new(PartKind::LeftLeg, [-1.5, 5., 1.])
+                .cuboid([-3., 0., -0.5], [0., 5., 2.5], [26., 0.]),
+        ]));
+        let mut spider_body = ModelPart::new(PartKind::Body, [0., 8., 0.])
+            .cuboid([-3., 5., -3.], [3., 11., 3.], [0., 0.])
+            .cuboid([-5., 4., 3.], [5., 12., 15.], [0., 12.]);
+        for z in [-2., 0., 2., 4.] {
+            spider_body = spider_body
+                .cuboid([3., 7., z - 1.], [19., 9., z + 1.], [18., 0.])
+                .cuboid([-19., 7., z - 1.], [-3., 9., z + 1.], [18., 0.]);
+        }
+        models.insert("spider".to_string(), EntityModel::new(ModelTexture::File("spider.png"), vec![
+            ModelPart::new(PartKind::Head, [0., 8., -3.])
+                .cuboid([-4., 4., -11.], [4., 12., -3.], [32., 4.]),
+            spider_body,
+        ]));
+        Self { models, blocks: BlockMeshDatabase::default() }
+    }
+}
+impl ModelRegistry {
+    /// Looks up a model. Names may carry a scale after
+    /// a `|`, and block IDs give a block model. Unknown
+    /// names fall back to the humanoid.
+    pub fn get(&mut self, name: &str) -> &EntityModel {
+        let name = name.to_lowercase();
+        if !self.models.contains_key(&name) {
+            let model = self.build(&name);
+            self.models.insert(name.clone(), model);
+        }
+        &self.models[&name]
+    }
+    fn build(&self, name: &str) -> EntityModel {
+        let (base, scale) = match name.split_once('|') {
+            Some((base, scale)) => (base, scale.parse::<f32>().ok()),
+            None => (name, None),
+        };
+        let model = if let Some(model) = self.models.get(base) {
+            model.clone()
+        } else if let Ok(id) = base.parse::<BlockId>() {
+            EntityModel::block(&self.blocks, id)
+        } else {
+            log::info!("Unknown model {}, using humanoid", base);
+            self.models["humanoid"].clone()
+        };
+        match scale {
+            Some(scale) => {
+                let scale = model.scale * scale;
+                model.scaled(scale)
+            }
+            None => model,
+        }
+    }
+}
+
+/// Per-entity appearance set through ChangeModel and EntityProperty.
+#[derive(Clone, Debug)]
+pub struct EntityAppearance {
+    pub model: String,
+    /// Extra rotation, in degrees.
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+impl Default for EntityAppearance {
+    fn default() -> Self {
+        Self {
+            model: "humanoid".to_string(),
+            rotation: Vec3::ZERO,
+            scale: Vec3::ONE,
+        }
+    }
+}
+impl EntityAppearance {
+    /// Applies an EntityProperty. Rotations are in degrees,
+    /// scales in thousandths.
+    pub fn set_property(&mut self, property: u8, value: i32) {
+        match property {
+            0 => self.rotation.x = value as f32,
+            1 => self.rotation.y = value as f32,
+            2 => self.rotation.z = value as f32,
+            3..=5 => {
+                let scale = (value as f32 / 1000.).clamp(0.01, 4.);
+                self.scale[property as usize - 3] = scale;
+            }
+            _ => log::info!("Unknown entity property {}", property),
+        }
+    }
+    /// Model space to entity space, before the
+    /// entity's own position and yaw are applied.
+    pub fn transform(&self, model: &EntityModel) -> Mat4 {
+        let r = self.rotation * (std::f32::consts::PI / 180.);
+        Mat4::from_rotation_z(r.z)
+            * Mat4::from_rotation_x(r.x)
+            * Mat4::from_rotation_y(r.y)
+            * Mat4::from_scale(self.scale * model.scale)
+    }
+}
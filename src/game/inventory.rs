@@ -0,0 +1,123 @@
+use super::world::BlockId;
+
+/// Number of hotbar slots.
+pub const HOTBAR_SIZE: usize = 9;
+
+/// Highest block ID shown in the picker by default.
+const MAX_DEFAULT_BLOCK: BlockId = 65;
+
+/// The player's blocks: a hotbar, the selected slot
+/// and the order blocks appear in the block picker.
+pub struct Inventory {
+    hotbar: [BlockId; HOTBAR_SIZE],
+    selected: usize,
+    /// Set by the server through HoldThis to
+    /// stop the player changing their block.
+    locked: bool,
+    /// Blocks in the picker, in display order.
+    /// Hidden blocks are left out.
+    order: Vec<BlockId>,
+    /// Index into `order` highlighted in
+    /// the block picker, if it is open.
+    picker: Option<usize>,
+}
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            // Stone, cobblestone, brick, dirt, planks, log, leaves, grass, slab.
+            hotbar: [1, 4, 45, 3, 5, 17, 18, 2, 44],
+            selected: 0,
+            locked: false,
+            order: (1..=MAX_DEFAULT_BLOCK).collect(),
+            picker: None,
+        }
+    }
+}
+impl Inventory {
+    /// The block the player is holding.
+    pub fn held_block(&self) -> BlockId {
+        self.hotbar[self.selected]
+    }
+    pub fn hotbar(&self) -> &[BlockId; HOTBAR_SIZE] {
+        &self.hotbar
+    }
+    pub fn selected_slot(&self) -> usize {
+        self.selected
+    }
+    /// Selects a hotbar slot, unless the server locked it.
+    pub fn select_slot(&mut self, slot: usize) {
+        if !self.locked && slot < HOTBAR_SIZE {
+            self.selected = slot;
+        }
+    }
+    /// Moves the selected slot by `offset`, wrapping around.
+    pub fn scroll(&mut self, offset: i32) {
+        let slot = (self.selected as i32 + offset).rem_euclid(HOTBAR_SIZE as i32);
+        self.select_slot(slot as usize);
+    }
+    /// Handles HoldThis: holds `block` regardless of the
+    /// lock, then locks or unlocks changing it.
+    pub fn hold_this(&mut self, block: BlockId, prevent_change: bool) {
+        self.force_held_block(block);
+        self.locked = prevent_change;
+    }
+    fn force_held_block(&mut self, block: BlockId) {
+        if let Some(slot) = self.hotbar.iter().position(|&b| b == block) {
+            self.selected = slot;
+        } else {
+            self.hotbar[self.selected] = block;
+        }
+    }
+    /// Handles SetHotbar.
+    pub fn set_hotbar(&mut self, slot: usize, block: BlockId) {
+        if let Some(s) = self.hotbar.get_mut(slot) {
+            *s = block;
+        }
+    }
+    /// Handles InventoryOrder. `order` is the 1-based position of
+    /// `block` in the picker, or 0 to hide it.
+    pub fn set_order(&mut self, block: BlockId, order: usize) {
+        self.order.retain(|&b| b != block);
+        if order > 0 {
+            let index = (order - 1).min(self.order.len());
+            self.order.insert(index, block);
+        }
+        if let Some(cursor) = self.picker.as_mut() {
+            *cursor = (*cursor).min(self.order.len().saturating_sub(1));
+        }
+    }
+    /// Blocks visible in the picker, in display order.
+    pub fn picker_blocks(&self) -> &[BlockId] {
+        &self.order
+    }
+    /// The highlighted index into `picker_blocks`,
+    /// or `None` if the picker is closed.
+    pub fn picker_cursor(&self) -> Option<usize> {
+        self.picker
+    }
+    /// Opens the picker on the held block, or closes it.
+    pub fn toggle_picker(&mut self) {
+        self.picker = match self.picker {
+            Some(_) => None,
+            None => Some(self.order.iter().position(|&b| b == self.held_block()).unwrap_or(0)),
+        };
+    }
+    /// Moves the picker highlight by `offset`, wrapping around.
+    pub fn move_picker(&mut self, offset: i32) {
+        if let Some(cursor) = self.picker.as_mut() {
+            if !self.order.is_empty() {
+                *cursor = (*cursor as i32 + offset).rem_euclid(self.order.len() as i32) as usize;
+            }
+        }
+    }
+    /// Holds the highlighted block and closes the picker.
+    pub fn pick(&mut self) {
+        if let Some(cursor) = self.picker.take() {
+            if let Some(&block) = self.order.get(cursor) {
+                if !self.locked {
+                    self.force_held_block(block);
+                }
+            }
+        }
+    }
+}
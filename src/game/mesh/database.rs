@@ -3,12 +3,12 @@ use enum_iterator::IntoEnumIterator;
 use fnv::FnvHashMap;
 use glam::{vec4, Vec4, Vec2, vec2, const_vec2};
 
-use crate::game::{world::Facing, texture::TerrainAtlas};
+use crate::game::{world::{Facing, BlockId}, texture::TerrainAtlas};
 
 use super::BlockMesh;
 
 pub struct BlockMeshDatabase {
-    map: FnvHashMap<u8, BlockMesh>,
+    map: FnvHashMap<BlockId, BlockMesh>,
 }
 impl Default for BlockMeshDatabase {
     fn default() -> Self {
@@ -46,7 +46,7 @@ impl Default for BlockMeshDatabase {
     }
 }
 impl BlockMeshDatabase {
-    pub fn get(&self, idx: u8) -> BlockMesh {
+    pub fn get(&self, idx: BlockId) -> BlockMesh {
         if let Some(v) = self.map.get(&idx) {
             return v.clone();
         }
@@ -21,6 +21,9 @@ impl BlockMesh {
     pub fn new(vertices: Vec<Vec4>, texcoords: Vec<Vec2>) -> Self {
         Self { vertices, texcoords }
     }
+    pub fn into_parts(self) -> (Vec<Vec4>, Vec<Vec2>) {
+        (self.vertices, self.texcoords)
+    }
     pub fn facing(a: Vec4, b: Vec4, c: Vec4) -> Facing {
         let x = -Self::surface_normal(a, b, c).normalize();
         Self::vec_facing(x)
@@ -106,8 +109,8 @@ impl Default for MeshBuilder {
             rayon::spawn(move || {
                 while let Ok(m) = recv.recv() {
                     if let MeshBuilderTask::BuildChunk { world, position, sender } = m {
-                        let world_data = world.data.read().unwrap();
                         let mut chunk_mesh = ChunkMesh::default();
+                        let blocks = world.blocks();
                         let x = position.x << 4;
                         let y = position.y << 4;
                         let z = position.z << 4;
@@ -115,7 +118,7 @@ impl Default for MeshBuilder {
                             for y in y..y + 16 {
                                 for z in z..z + 16 {
                                     let pos = BlockPosition::new(x as i32, y as i32, z as i32);
-                                    let block = world_data.get(world.pos_to_index(pos.x as usize, pos.y as usize, pos.z as usize)).copied().unwrap_or(0);
+                                    let block = blocks.get_block(pos);
                                     if block != 0 {
                                         let mut mesh = db.get(block);
                                         mesh.matrix(Mat4::from_translation(vec3(x as f32, y as f32, z as f32) * 0.5));
@@ -123,7 +126,7 @@ impl Default for MeshBuilder {
                                             let f = BlockMesh::facing(a, b, c);
                                             let p = pos.offset(f);
                                             //panic!("Offset {:?}", p);
-                                            let block = world.get_block(p.x as usize, p.y as usize, p.z as usize);
+                                            let block = blocks.get_block(p);
                                             let f = block == 0;
                                             f
                                         });
@@ -132,6 +135,7 @@ impl Default for MeshBuilder {
                                 }
                             }
                         }
+                        drop(blocks);
                         sender.send((position, chunk_mesh)).unwrap();
                     }
                 }
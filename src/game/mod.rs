@@ -1,20 +1,36 @@
 use std::time::Instant;
 
-use glam::{vec2, vec3};
+use chrono::Local;
+use fnv::FnvHashMap;
+use glam::{vec2, vec3, Vec3};
 use glutin::{
     dpi::{PhysicalSize, Size},
-    event::{Event, VirtualKeyCode, WindowEvent},
+    event::{Event, VirtualKeyCode, WindowEvent, MouseButton},
 };
 
 use crate::render::window::GameWindow;
 
 use self::{
-    camera::Camera, engine::GameEngine, network::client::{Client, handle, packet::ServerPlayPacket}, render_stages::RenderManager,
-    world::{World, ChunkPosition, Block},
+    chat::Chat,
+    entity::{Entity, TeleportBehavior, model::{ModelRegistry, EntityAppearance}},
+    inventory::{Inventory, HOTBAR_SIZE},
+    permissions::BlockPermissions,
+    physics::block_bounds,
+    prediction::PendingBlocks,
+    player::{MovementInput, Player, Spawnpoint, from_network_position},
+    camera::{Camera, angle_from_byte, angle_to_byte}, engine::GameEngine, network::client::{Client, ClickTarget, handle, packet::ServerPlayPacket}, render_stages::{RenderManager, text::{TextSegment, FONT_SIZE}},
+    world::{World, ChunkPosition, cw::{ClassicWorld, ClassicWorldError, MapAppearance, WorldSpawn}, generators, import, Block, BlockId, BlockPosition, block_name, block_collision, block_to_render, BlockCollision, render_to_block, raycast::{targetable, RayHit}, schematic::Schematic, BLOCK_SIZE},
 };
 
 mod camera;
+mod chat;
+mod entity;
 pub mod engine;
+mod inventory;
+mod permissions;
+mod physics;
+mod prediction;
+mod player;
 mod mesh;
 mod network;
 mod render_stages;
@@ -23,21 +39,171 @@ mod world;
 pub struct CubeGame {
     /// Camera.
     camera: Camera,
+    /// Local player movement.
+    player: Player,
     /// Network client.
     client: Client,
     /// World.
     world: World,
     /// Render manager.
     render_manager: RenderManager,
+    /// How far away blocks can be
+    /// clicked, in blocks.
+    click_distance: f32,
+    /// Hotbar and block picker.
+    inventory: Inventory,
+    /// Chat history and colour codes.
+    chat: Chat,
+    /// Blocks the player may place and delete.
+    permissions: BlockPermissions,
+    /// Entity models by name.
+    models: ModelRegistry,
+    /// Models, rotations and scales set by the server, by entity ID.
+    appearances: FnvHashMap<u8, EntityAppearance>,
+    /// Other players, by entity ID.
+    entities: FnvHashMap<u8, Entity>,
+    /// Blocks changed locally which the server may still refuse.
+    pending_blocks: PendingBlocks,
+    /// First corner of the region being copied.
+    copy_corner: Option<BlockPosition>,
+    /// The last region copied, for pasting.
+    clipboard: Option<Schematic>,
 
     /// Removed on run
     _engine: Option<GameEngine<Self>>,
 }
 impl CubeGame {
+    /// Saves the current map, with its spawn
+    /// and surroundings, as a ClassicWorld file.
+    pub fn save_world(&self, path: &str) -> Result<(), ClassicWorldError> {
+        let mut level = ClassicWorld::new(self.world.clone());
+        let spawn = self.player.spawn;
+        let position = spawn.position.floor();
+        level.spawn = WorldSpawn {
+            position: BlockPosition::new(position.x as i32, position.y as i32, position.z as i32),
+            yaw: angle_to_byte(spawn.yaw),
+            pitch: angle_to_byte(spawn.pitch),
+        };
+        let edge = self.render_manager.edge.borrow().edge;
+        level.map_appearance = Some(MapAppearance {
+            texture_url: String::new(),
+            side_block: edge.side_block as u8,
+            edge_block: edge.edge_block as u8,
+            side_level: edge.edge_height.map_or(-1, |height| height as i16),
+        });
+        level.save(path)
+    }
     pub fn set_block(&mut self, block: Block) {
-        let mut world_render = self.render_manager.world.borrow_mut();
-        self.world.set_block(block);
-        world_render.build_chunk(self.world.clone(), block.position.to_chunk());
+        self.set_blocks([block]);
+    }
+    /// Sets many blocks at once. The changed chunks are
+    /// remeshed by the world renderer on the next frame.
+    pub fn set_blocks(&mut self, blocks: impl IntoIterator<Item = Block>) {
+        let mut weather = self.render_manager.weather.borrow_mut();
+        for block in blocks {
+            if let Err(e) = self.world.set_block(block) {
+                log::warn!("Ignoring block change: {}", e);
+                continue;
+            }
+            weather.invalidate_column(block.position.x, block.position.z);
+        }
+    }
+    /// The block the player is looking at, within `click_distance`.
+    pub fn target_block(&self) -> Option<RayHit> {
+        let origin = render_to_block(self.camera.position);
+        self.world.raycast(origin, self.camera.forward(), self.click_distance, targetable)
+    }
+    /// Places `block` at `position`, or deletes the block there if
+    /// `place` is false, and asks the server to do the same. Changes
+    /// forbidden by the permission table, outside the world, or putting
+    /// a solid block inside the player are refused. Returns whether
+    /// the change was made.
+    pub fn change_block(&mut self, position: BlockPosition, place: bool, block: BlockId) -> bool {
+        let Some(existing) = self.world.get(position) else {
+            return false;
+        };
+        let allowed = if place {
+            self.permissions.can_place(block) && !(block_collision(block) == BlockCollision::Solid && self.player.bounds().intersects(&block_bounds(position)))
+        } else {
+            self.permissions.can_delete(existing)
+        };
+        if allowed {
+            let id = if place { block } else { 0 };
+            self.set_block(Block::new(id, position));
+            self.pending_blocks.insert(position, id);
+            self.client.set_block(position, place, block);
+        }
+        allowed
+    }
+    /// Copies the region between the corners marked with the targeted
+    /// block. The first call marks a corner, and the second copies the
+    /// region to the clipboard and saves it to `path`.
+    pub fn copy_region(&mut self, path: &str) {
+        let Some(hit) = self.target_block() else {
+            return;
+        };
+        let Some(corner) = self.copy_corner.take() else {
+            self.copy_corner = Some(hit.position);
+            self.chat.add_message("&eMarked a corner, now mark the opposite one".to_string());
+            return;
+        };
+        let schematic = match Schematic::from_region(&self.world, corner, hit.position) {
+            Ok(schematic) => schematic,
+            Err(e) => return self.chat.add_message(format!("&cFailed to copy region: {}", e)),
+        };
+        let (width, height, length) = (schematic.width(), schematic.height(), schematic.length());
+        match schematic.save(path) {
+            Ok(()) => self.chat.add_message(format!("&eCopied {}x{}x{} region and saved it to {}", width, height, length, path)),
+            Err(e) => self.chat.add_message(format!("&cCopied region but failed to save it: {}", e)),
+        }
+        self.clipboard = Some(schematic);
+    }
+    /// Pastes the clipboard against the targeted face, through
+    /// `change_block` so the server is told and permissions apply.
+    /// Blocks already matching are skipped.
+    pub fn paste_region(&mut self) {
+        let (Some(hit), Some(schematic)) = (self.target_block(), self.clipboard.take()) else {
+            return;
+        };
+        // Deletes are sent with the held block, as the client does for clicks.
+        let held = self.inventory.held_block();
+        let mut changed = 0;
+        for (position, block) in schematic.blocks_at(hit.position.offset(hit.face)) {
+            let place = block != 0;
+            if self.world.get(position).is_some_and(|existing| existing != block) && self.change_block(position, place, if place { block } else { held }) {
+                changed += 1;
+            }
+        }
+        self.chat.add_message(format!("&ePasted {} blocks", changed));
+        self.clipboard = Some(schematic);
+    }
+    /// Applies a SetBlock from the server. This rolls back a
+    /// local change if the server disagrees with it.
+    pub fn server_set_block(&mut self, block: Block) {
+        match self.pending_blocks.apply(&mut self.world, block) {
+            Ok(true) => self.render_manager.weather.borrow_mut().invalidate_column(block.position.x, block.position.z),
+            Ok(false) => (),
+            Err(e) => log::warn!("Ignoring block change: {}", e),
+        }
+    }
+    /// Handles an ExtEntityTeleport aimed at the local player.
+    pub fn ext_teleport_self(&mut self, behavior: TeleportBehavior, position: (i32, i32, i32), yaw: u8, pitch: u8) {
+        let (x, y, z) = position;
+        match behavior.position {
+            // Relative moves keep the player's velocity.
+            Some(mode) if mode.is_relative() => self.player.position += vec3(x as f32, y as f32, z as f32) / 32.,
+            Some(_) => self.player.teleport(from_network_position(x, y, z)),
+            None => (),
+        }
+        let mut yaw_degrees = self.camera.classic_yaw();
+        let mut pitch_degrees = self.camera.classic_pitch();
+        if behavior.yaw {
+            yaw_degrees = angle_from_byte(yaw);
+        }
+        if behavior.pitch {
+            pitch_degrees = angle_from_byte(pitch);
+        }
+        self.camera.set_classic_orientation(yaw_degrees, pitch_degrees);
     }
     pub async fn new(window_size: impl Into<Size> + Copy) -> Self {
         let (client, package) = Client::connect("127.0.0.1:25565", String::from("Exo"))
@@ -54,6 +220,16 @@ impl CubeGame {
         ctx.enable(gl::CULL_FACE);
         engine.renderer().window.set_cursor_grabbed(true).unwrap();
         engine.add_event_handler(move_camera);
+        engine.add_event_handler(player_click);
+        engine.add_event_handler(inventory_input);
+        engine.add_event_handler(|engine, _window, cube, event| {
+            if let Event::MainEventsCleared = event {
+                let delta = engine.delta_time().as_secs_f32();
+                for entity in cube.entities.values_mut() {
+                    entity.update(delta);
+                }
+            }
+        });
         engine.add_event_handler(|engine, window, cube, event| {
             if let Event::MainEventsCleared = event {
                 if engine.input.keyboard.was_pressed(VirtualKeyCode::X) {
@@ -62,9 +238,28 @@ impl CubeGame {
                 }
             }
         });
+        engine.add_event_handler(|engine, _window, cube, event| {
+            if let Event::MainEventsCleared = event {
+                if engine.input.keyboard.was_pressed(VirtualKeyCode::F5) {
+                    let path = format!("{}.cw", Local::now().format("%Y-%m-%d_%H-%M-%S"));
+                    match cube.save_world(&path) {
+                        Ok(()) => cube.chat.add_message(format!("&eSaved map to {}", path)),
+                        Err(e) => cube.chat.add_message(format!("&cFailed to save map: {}", e)),
+                    }
+                }
+                if engine.input.keyboard.was_pressed(VirtualKeyCode::F6) {
+                    cube.copy_region(&format!("{}.schematic", Local::now().format("%Y-%m-%d_%H-%M-%S")));
+                }
+                if engine.input.keyboard.was_pressed(VirtualKeyCode::F7) {
+                    cube.paste_region();
+                }
+            }
+        });
         engine.add_event_handler(|engine, window, cube, event| {
             if let Event::MainEventsCleared = event {
-                cube.client.update_position(cube.camera.position.x, cube.camera.position.y, cube.camera.position.z, 0, 0);
+                let held_block = cube.inventory.held_block();
+                let (yaw, pitch) = (cube.camera.classic_yaw(), cube.camera.classic_pitch());
+                cube.client.update_position(cube.player.position, yaw, pitch, held_block);
             }
         });
         engine.add_event_handler(|engine, window, cube, event| {
@@ -92,11 +287,12 @@ impl CubeGame {
                     frames_per_second = 0.;
                 }
                 cube.render_manager.text.borrow_mut().render(
-                    format!("FPS: {}", fps),
+                    vec![TextSegment::new(format!("FPS: {}", fps), vec3(1., 1., 1.))],
                     vec2(25., (window.size().height) as f32 - 40.),
                     0.5,
-                    vec3(1., 1., 1.),
                 );
+                draw_chat(cube);
+                draw_nameplates(cube);
             }
         });
 
@@ -111,8 +307,13 @@ impl CubeGame {
 
 
         let render_manager = RenderManager::new(&mut engine);
-        let mut world = package.world;
-        let mut world = World::from_file("big.cw").unwrap();
+        let (world, spawn) = match generate_configured_world(&package.world) {
+            Some(world) => (world, None),
+            None => {
+                let level = import::load_map("big.cw").unwrap();
+                (level.world, Some(level.spawn))
+            },
+        };
         let mut worldrender = render_manager.world.borrow_mut();
         for x in 0..(world.length() >> 4) + 1 {
             for y in 0..(world.height() >> 4) + 1 {
@@ -123,19 +324,70 @@ impl CubeGame {
         }
         drop(worldrender);
         let size: PhysicalSize<i32> = window_size.into().to_physical(1.);
-        Self {
+        let mut game = Self {
             client,
             world,
             _engine: Some(engine),
             camera: Camera::new(45.5, size.width, size.height),
+            player: Player::default(),
             render_manager,
+            click_distance: 5.,
+            inventory: Inventory::default(),
+            chat: Chat::default(),
+            permissions: BlockPermissions::default(),
+            models: ModelRegistry::default(),
+            appearances: FnvHashMap::default(),
+            entities: FnvHashMap::default(),
+            pending_blocks: PendingBlocks::default(),
+            copy_corner: None,
+            clipboard: None,
+        };
+        if let Some(spawn) = spawn {
+            game.spawn_at(spawn);
         }
+        game
+    }
+    /// Makes a map's saved spawn the player's spawnpoint
+    /// and moves them there, as SpawnPlayer does.
+    fn spawn_at(&mut self, spawn: WorldSpawn) {
+        let position = spawn.position;
+        let spawn = Spawnpoint {
+            // Centred in the block, which saving floors back to.
+            position: vec3(position.x as f32 + 0.5, position.y as f32, position.z as f32 + 0.5),
+            yaw: angle_from_byte(spawn.yaw),
+            pitch: angle_from_byte(spawn.pitch),
+        };
+        self.player.spawn = spawn;
+        self.player.teleport(spawn.position);
+        self.camera.set_classic_orientation(spawn.yaw, spawn.pitch);
     }
     pub fn run(mut self) {
         self._engine.take().unwrap().run(self);
     }
 }
 
+/// Generates a map the size of the server's when `EXO_GENERATOR` names
+/// a theme, optionally followed by a seed, as in `island:42`.
+fn generate_configured_world(server_world: &World) -> Option<World> {
+    let config = std::env::var("EXO_GENERATOR").ok()?;
+    let (name, seed) = config.split_once(':').unwrap_or((&config, ""));
+    let seed = match seed.parse() {
+        Ok(seed) => seed,
+        Err(_) => {
+            if !seed.is_empty() {
+                log::warn!("Seed {:?} is not a number, using a random one", seed);
+            }
+            Local::now().timestamp()
+        },
+    };
+    let Some(generator) = generators::theme(name, seed, server_world.height()) else {
+        log::warn!("Unknown generator {:?}, expected one of {}", name, generators::THEMES.join(", "));
+        return None;
+    };
+    log::info!("Generating a {} map with seed {}", name, seed);
+    Some(World::new(generator, server_world.width(), server_world.height(), server_world.length()))
+}
+
 fn move_camera(
     engine: &mut GameEngine<CubeGame>,
     window: &mut GameWindow,
@@ -143,20 +395,33 @@ fn move_camera(
     event: &Event<()>,
 ) {
     if let Event::MainEventsCleared = event {
-        let SPEED: f32 = 3. * 1000.;
-        let SPEED = SPEED * engine.delta_time().as_secs_f32();
-        if engine.input.keyboard.is_pressed(VirtualKeyCode::W) {
-            cube.camera.position -= cube.camera.direction() * SPEED;
-        }
-        if engine.input.keyboard.is_pressed(VirtualKeyCode::S) {
-            cube.camera.position += cube.camera.direction() * SPEED;
+        let delta = engine.delta_time().as_secs_f32();
+        let keyboard = &mut engine.input.keyboard;
+        let forward = cube.camera.forward() * vec3(1., 0., 1.);
+        let left = cube.camera.left() * vec3(1., 0., 1.);
+        let right = cube.camera.right() * vec3(1., 0., 1.);
+        let mut direction = Vec3::ZERO;
+        for (key, towards) in [(VirtualKeyCode::W, forward), (VirtualKeyCode::S, -forward), (VirtualKeyCode::A, left), (VirtualKeyCode::D, right)] {
+            if keyboard.is_pressed(key) {
+                direction += towards;
+            }
         }
-        if engine.input.keyboard.is_pressed(VirtualKeyCode::A) {
-            cube.camera.position += cube.camera.left() * SPEED;
+        let input = MovementInput {
+            direction: direction.normalize_or_zero(),
+            jump: keyboard.is_pressed(VirtualKeyCode::Space),
+            descend: keyboard.is_pressed(VirtualKeyCode::LShift),
+        };
+        let player = &mut cube.player;
+        if keyboard.was_pressed(VirtualKeyCode::Z) {
+            player.flying = !player.flying;
+            player.velocity = Vec3::ZERO;
         }
-        if engine.input.keyboard.is_pressed(VirtualKeyCode::D) {
-            cube.camera.position += cube.camera.right() * SPEED;
+        if keyboard.was_pressed(VirtualKeyCode::R) {
+            let spawn = player.respawn();
+            cube.camera.set_classic_orientation(spawn.yaw, spawn.pitch);
         }
+        player.update(delta, &cube.world, &input);
+        cube.camera.position = block_to_render(player.smoothed_eye_position());
         const SENSITIVITY: f32 = 0.1;
         let mouse = &mut engine.input.mouse;
         let camera = &mut cube.camera;
@@ -171,3 +436,158 @@ fn move_camera(
         }
     }
 }
+
+fn player_click(
+    engine: &mut GameEngine<CubeGame>,
+    window: &mut GameWindow,
+    cube: &mut CubeGame,
+    event: &Event<()>,
+) {
+    if let Event::MainEventsCleared = event {
+        if !window.cursor_grabbed() || cube.inventory.picker_cursor().is_some() {
+            return;
+        }
+        let mouse = &mut engine.input.mouse;
+        for (id, button) in [(0, MouseButton::Left), (1, MouseButton::Right), (2, MouseButton::Middle)] {
+            for (pressed, changed) in [(true, mouse.was_pressed(button)), (false, mouse.was_released(button))] {
+                if changed {
+                    let (yaw, pitch) = (cube.camera.classic_yaw(), cube.camera.classic_pitch());
+                    let target = ClickTarget {
+                        entity: None,
+                        block: cube.target_block().map(|hit| (hit.position, hit.face)),
+                    };
+                    cube.client.player_click(id, pressed, yaw, pitch, target);
+                    if let (true, Some(hit)) = (pressed, cube.target_block()) {
+                        match button {
+                            MouseButton::Left => {
+                                let block = cube.inventory.held_block();
+                                cube.change_block(hit.position, false, block);
+                            },
+                            MouseButton::Right => {
+                                let block = cube.inventory.held_block();
+                                cube.change_block(hit.position.offset(hit.face), true, block);
+                            },
+                            _ => (),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Columns in the block picker.
+const PICKER_COLUMNS: usize = 6;
+
+fn inventory_input(
+    engine: &mut GameEngine<CubeGame>,
+    window: &mut GameWindow,
+    cube: &mut CubeGame,
+    event: &Event<()>,
+) {
+    if let Event::MainEventsCleared = event {
+        const SLOT_KEYS: [VirtualKeyCode; HOTBAR_SIZE] = [
+            VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+            VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+        ];
+        let keyboard = &mut engine.input.keyboard;
+        let inventory = &mut cube.inventory;
+        for (slot, key) in SLOT_KEYS.into_iter().enumerate() {
+            if keyboard.was_pressed(key) {
+                inventory.select_slot(slot);
+            }
+        }
+        if keyboard.was_pressed(VirtualKeyCode::B) {
+            inventory.toggle_picker();
+        }
+        let scroll = engine.input.mouse.take_scroll();
+        if inventory.picker_cursor().is_some() {
+            let mut offset = -scroll;
+            if keyboard.was_pressed(VirtualKeyCode::Right) {
+                offset += 1;
+            }
+            if keyboard.was_pressed(VirtualKeyCode::Left) {
+                offset -= 1;
+            }
+            if keyboard.was_pressed(VirtualKeyCode::Down) {
+                offset += PICKER_COLUMNS as i32;
+            }
+            if keyboard.was_pressed(VirtualKeyCode::Up) {
+                offset -= PICKER_COLUMNS as i32;
+            }
+            inventory.move_picker(offset);
+            if keyboard.was_pressed(VirtualKeyCode::Return) || engine.input.mouse.was_pressed(MouseButton::Left) {
+                inventory.pick();
+            }
+        } else {
+            inventory.scroll(-scroll);
+        }
+        draw_inventory(window, cube);
+    }
+}
+
+fn draw_inventory(window: &mut GameWindow, cube: &mut CubeGame) {
+    const SCALE: f32 = 0.35;
+    const WIDTH: f32 = 130.;
+    let mut text = cube.render_manager.text.borrow_mut();
+    let inventory = &cube.inventory;
+    let white = vec3(1., 1., 1.);
+    let highlight = vec3(1., 1., 0.3);
+    let forbidden = vec3(0.45, 0.45, 0.45);
+    let permissions = &cube.permissions;
+    // Blocks the player may not place are greyed out.
+    let color_of = |block, highlighted| match (permissions.can_place(block), highlighted) {
+        (false, true) => forbidden * 1.5,
+        (false, false) => forbidden,
+        (true, true) => highlight,
+        (true, false) => white,
+    };
+    for (slot, &block) in inventory.hotbar().iter().enumerate() {
+        let color = color_of(block, slot == inventory.selected_slot());
+        text.render(vec![TextSegment::new(block_name(block), color)], vec2(25. + slot as f32 * WIDTH, 25.), SCALE);
+    }
+    if let Some(cursor) = inventory.picker_cursor() {
+        let top = window.size().height as f32 - 100.;
+        for (i, &block) in inventory.picker_blocks().iter().enumerate() {
+            let color = color_of(block, i == cursor);
+            let position = vec2(
+                100. + (i % PICKER_COLUMNS) as f32 * WIDTH * 1.3,
+                top - (i / PICKER_COLUMNS) as f32 * 25.,
+            );
+            text.render(vec![TextSegment::new(block_name(block), color)], position, SCALE);
+        }
+    }
+}
+
+fn draw_chat(cube: &mut CubeGame) {
+    const SCALE: f32 = 0.4;
+    const LINE_HEIGHT: f32 = 22.;
+    let mut text = cube.render_manager.text.borrow_mut();
+    // Newest line sits just above the hotbar.
+    for (i, line) in cube.chat.lines().rev().enumerate() {
+        let segments = cube.chat.colors.parse(line, vec3(1., 1., 1.));
+        text.render(segments, vec2(25., 60. + i as f32 * LINE_HEIGHT), SCALE);
+    }
+}
+
+fn draw_nameplates(cube: &mut CubeGame) {
+    /// Height of a name above the feet of a one block tall model, in blocks.
+    const HEIGHT: f32 = 2.1;
+    /// Height of a line of text, in blocks.
+    const SIZE: f32 = 0.25;
+    /// Past this many blocks away, names grow to stay readable.
+    const FAR: f32 = 16.;
+    let mut text = cube.render_manager.text.borrow_mut();
+    let eye = cube.player.eye_position();
+    for (id, entity) in cube.entities.iter() {
+        let scale = match cube.appearances.get(id) {
+            Some(appearance) => cube.models.get(&appearance.model.to_lowercase()).scale * appearance.scale.y,
+            None => 1.,
+        };
+        let anchor = entity.position + vec3(0., HEIGHT * scale, 0.);
+        let size = SIZE * (anchor.distance(eye) / FAR).max(1.);
+        let segments = cube.chat.colors.parse(&entity.name, vec3(1., 1., 1.));
+        text.render_world(segments, block_to_render(anchor), size * BLOCK_SIZE / FONT_SIZE as f32);
+    }
+}
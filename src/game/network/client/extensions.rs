@@ -0,0 +1,52 @@
+use fnv::FnvHashSet;
+
+/// Name sent in our `ExtInfo` packet.
+pub const APP_NAME: &str = "exoglgame";
+
+/// Magic value placed in the unused byte of
+/// `PlayerIdentification` to advertise CPE support.
+pub const CPE_MAGIC: u8 = 0x42;
+
+/// Every Classic Protocol Extension the client
+/// understands, along with its version.
+pub const SUPPORTED_EXTENSIONS: &[(&str, i32)] = &[
+    ("SelectionCuboid", 1),
+    ("BulkBlockUpdate", 1),
+    ("FastMap", 1),
+    ("ExtendedBlocks", 1),
+    ("ClickDistance", 1),
+    ("PlayerClick", 1),
+    ("HeldBlock", 1),
+    ("SetHotbar", 1),
+    ("InventoryOrder", 1),
+    ("ChangeModel", 1),
+    ("EntityProperty", 1),
+    ("SetSpawnpoint", 1),
+    ("VelocityControl", 1),
+    ("ExtEntityTeleport", 1),
+    ("CustomParticles", 1),
+    ("TextColors", 1),
+    ("EnvWeatherType", 1),
+    ("BlockPermissions", 1),
+    ("EnvMapAppearance", 1),
+    ("EnvMapAspect", 1),
+];
+
+/// Extensions agreed upon with the server.
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionSet {
+    extensions: FnvHashSet<String>,
+}
+impl ExtensionSet {
+    /// Marks an extension announced by the server
+    /// as negotiated, if we support the same version.
+    pub fn negotiate(&mut self, name: &str, version: i32) {
+        if SUPPORTED_EXTENSIONS.iter().any(|(n, v)| *n == name && *v == version) {
+            self.extensions.insert(name.to_string());
+        }
+    }
+    /// Returns true if both sides support `name`.
+    pub fn has(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+}
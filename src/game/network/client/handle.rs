@@ -1,6 +1,8 @@
 use std::any;
 
-use crate::game::{CubeGame, world::{Block, BlockPosition}};
+use glam::{vec3, vec4};
+
+use crate::game::{CubeGame, entity::{Entity, MoveMode, TeleportBehavior, interpolation::EntityState}, world::{Block, BlockId, BlockPosition}, render_stages::{selection::Selection, particles::ParticleEffect, weather::WeatherType}, camera::angle_from_byte, player::{from_network_position, Spawnpoint, VelocityMode}};
 
 use super::packet::ServerPlayPacket;
 
@@ -8,19 +10,213 @@ pub fn handle_packet(game: &mut CubeGame, packet: ServerPlayPacket) -> anyhow::R
     match packet {
         ServerPlayPacket::Ping(_) => (),
         ServerPlayPacket::Message(p) => {
-            log::info!("Message {}", p.message);
+            game.chat.add_message(p.message);
+        },
+        ServerPlayPacket::SpawnPlayer(packet) => {
+            let position = from_network_position(packet.x as i32, packet.y as i32, packet.z as i32);
+            let (yaw, pitch) = (angle_from_byte(packet.yaw as u8), angle_from_byte(packet.pitch as u8));
+            if packet.player_id == -1 {
+                let spawn = Spawnpoint { position, yaw, pitch };
+                game.player.spawn = spawn;
+                game.player.teleport(spawn.position);
+                game.camera.set_classic_orientation(spawn.yaw, spawn.pitch);
+            } else {
+                let entity = Entity::new(packet.player_name, EntityState::new(position, yaw, pitch));
+                game.entities.insert(packet.player_id as u8, entity);
+            }
+        },
+        ServerPlayPacket::DespawnPlayer(p) => {
+            game.entities.remove(&(p.player_id as u8));
+            game.appearances.remove(&(p.player_id as u8));
         },
-        ServerPlayPacket::SpawnPlayer(_) => (),
         ServerPlayPacket::PlayerTeleport(packet) => {
+            let position = from_network_position(packet.x as i32, packet.y as i32, packet.z as i32);
+            let (yaw, pitch) = (angle_from_byte(packet.yaw as u8), angle_from_byte(packet.pitch as u8));
             if packet.player_id == -1 {
-                let cam = &mut game.camera;
-                cam.position.x = (packet.x as f32) / 32.; 
-                cam.position.y = (packet.y as f32) / 32.; 
-                cam.position.z = (packet.z as f32) / 32.; 
+                game.player.teleport(position);
+                game.camera.set_classic_orientation(yaw, pitch);
+            } else if let Some(entity) = game.entities.get_mut(&(packet.player_id as u8)) {
+                // Servers also use this for ordinary movement; far moves still snap.
+                entity.move_to(EntityState::new(position, yaw, pitch));
+            }
+        },
+        ServerPlayPacket::PositionAndOrientationUpdate(p) => {
+            if let Some(entity) = game.entities.get_mut(&(p.player_id as u8)) {
+                let target = entity.target();
+                let position = target.position + vec3(p.dx as f32, p.dy as f32, p.dz as f32) / 32.;
+                entity.move_to(EntityState::new(position, angle_from_byte(p.yaw), angle_from_byte(p.pitch)));
+            }
+        },
+        ServerPlayPacket::PositionUpdate(p) => {
+            if let Some(entity) = game.entities.get_mut(&(p.player_id as u8)) {
+                let mut target = entity.target();
+                target.position += vec3(p.dx as f32, p.dy as f32, p.dz as f32) / 32.;
+                entity.move_to(target);
+            }
+        },
+        ServerPlayPacket::OrientationUpdate(p) => {
+            if let Some(entity) = game.entities.get_mut(&(p.player_id as u8)) {
+                let target = entity.target();
+                entity.move_to(EntityState::new(target.position, angle_from_byte(p.yaw), angle_from_byte(p.pitch)));
+            }
+        },
+        ServerPlayPacket::SetSpawnpoint(p) => {
+            game.player.spawn = Spawnpoint {
+                position: from_network_position(p.x as i32, p.y as i32, p.z as i32),
+                yaw: angle_from_byte(p.yaw),
+                pitch: angle_from_byte(p.pitch),
+            };
+        },
+        ServerPlayPacket::VelocityControl(p) => {
+            let velocity = vec3(p.x as f32, p.y as f32, p.z as f32) / 10000.;
+            let modes = [p.x_mode, p.y_mode, p.z_mode].map(VelocityMode::from_byte);
+            game.player.apply_velocity(velocity, modes);
+        },
+        ServerPlayPacket::ExtEntityTeleport(p) => {
+            let behavior = TeleportBehavior::from_byte(p.behavior);
+            if p.entity_id == 255 {
+                game.ext_teleport_self(behavior, (p.x as i32, p.y as i32, p.z as i32), p.yaw, p.pitch);
+            } else if let Some(entity) = game.entities.get_mut(&p.entity_id) {
+                let offset = vec3(p.x as f32, p.y as f32, p.z as f32) / 32.;
+                if behavior.position == Some(MoveMode::RelativeSeamless) {
+                    entity.shift(offset);
+                }
+                let mut target = entity.target();
+                match behavior.position {
+                    Some(MoveMode::AbsoluteInstant | MoveMode::AbsoluteSmooth) => {
+                        target.position = from_network_position(p.x as i32, p.y as i32, p.z as i32);
+                    }
+                    Some(MoveMode::RelativeSmooth) => target.position += offset,
+                    _ => (),
+                }
+                if behavior.yaw {
+                    target.yaw = angle_from_byte(p.yaw);
+                }
+                if behavior.pitch {
+                    target.pitch = angle_from_byte(p.pitch);
+                }
+                // Position and orientation each either snap or move smoothly.
+                let snap_position = behavior.position == Some(MoveMode::AbsoluteInstant);
+                let snap_orientation = behavior.yaw && !behavior.interpolate_orientation;
+                match (snap_position, snap_orientation) {
+                    (true, true) => entity.teleport(target),
+                    (false, false) => entity.move_to(target),
+                    (true, false) => {
+                        entity.teleport(EntityState::new(target.position, entity.yaw, entity.pitch));
+                        entity.move_to(target);
+                    }
+                    (false, true) => {
+                        entity.teleport(EntityState::new(entity.position, target.yaw, target.pitch));
+                        entity.move_to(target);
+                    }
+                }
             }
         },
         ServerPlayPacket::SetBlock(packet) => {
-            game.set_block(Block::new(packet.block_type, BlockPosition::new(packet.x as i32, packet.y as i32, packet.z as i32)));
+            game.server_set_block(Block::new(packet.block_type, BlockPosition::new(packet.x as i32, packet.y as i32, packet.z as i32)));
+        },
+        ServerPlayPacket::MakeSelection(p) => {
+            let selection = Selection::new(
+                vec3(p.start_x as f32, p.start_y as f32, p.start_z as f32),
+                vec3(p.end_x as f32, p.end_y as f32, p.end_z as f32),
+                vec4(p.red as f32, p.green as f32, p.blue as f32, p.alpha as f32),
+            );
+            game.render_manager.selection.borrow_mut().add_selection(p.selection_id, selection);
+        },
+        ServerPlayPacket::RemoveSelection(p) => {
+            game.render_manager.selection.borrow_mut().remove_selection(p.selection_id);
+        },
+        ServerPlayPacket::DefineEffect(p) => {
+            // Atlas coordinates are inclusive pixels of a 256x256 image.
+            let effect = ParticleEffect {
+                region: vec4(p.u1 as f32, p.v1 as f32, p.u2 as f32 + 1., p.v2 as f32 + 1.) / 256.,
+                tint: vec3(p.red as f32, p.green as f32, p.blue as f32) / 255.,
+                frame_count: p.frame_count,
+                particle_count: p.particle_count,
+                size: p.size as f32 / 32.,
+                size_variation: p.size_variation as f32 / 10000.,
+                spread: p.spread as f32 / 32.,
+                speed: p.speed as f32 / 10000.,
+                gravity: p.gravity as f32 / 10000.,
+                lifetime: p.base_lifetime as f32 / 10000.,
+                lifetime_variation: p.lifetime_variation as f32 / 10000.,
+                // FullBright only skips lighting, which isn't drawn.
+                collide_flags: p.collide_flags,
+            };
+            game.render_manager.particles.borrow_mut().define_effect(p.effect_id, effect);
+        },
+        ServerPlayPacket::SpawnEffect(p) => {
+            let position = vec3(p.x as f32, p.y as f32, p.z as f32) / 32.;
+            let origin = vec3(p.origin_x as f32, p.origin_y as f32, p.origin_z as f32) / 32.;
+            game.render_manager.particles.borrow_mut().spawn_effect(p.effect_id, position, origin);
+        },
+        ServerPlayPacket::SetTextColor(p) => {
+            game.chat.colors.set(p.code, p.red, p.green, p.blue);
+        },
+        ServerPlayPacket::EnvSetWeatherType(p) => {
+            match WeatherType::from_byte(p.weather_type) {
+                Some(weather) => game.render_manager.weather.borrow_mut().set_weather(weather),
+                None => log::warn!("Unknown weather type {}", p.weather_type),
+            }
+        },
+        ServerPlayPacket::SetBlockPermission(p) => {
+            game.permissions.set(p.block_type, p.allow_placement != 0, p.allow_deletion != 0);
+        },
+        ServerPlayPacket::EnvMapAppearance(p) => {
+            if !p.texture_url.is_empty() {
+                log::info!("Ignoring texture pack {}", p.texture_url);
+            }
+            let edge = &mut game.render_manager.edge.borrow_mut().edge;
+            edge.side_block = p.side_block as BlockId;
+            edge.edge_block = p.edge_block as BlockId;
+            edge.edge_height = if p.side_level < 0 { None } else { Some(p.side_level as i32) };
+        },
+        ServerPlayPacket::SetMapEnvUrl(p) => {
+            if !p.texture_url.is_empty() {
+                log::info!("Ignoring texture pack {}", p.texture_url);
+            }
+        },
+        ServerPlayPacket::SetMapEnvProperty(p) => {
+            let edge = &mut game.render_manager.edge.borrow_mut().edge;
+            match p.property {
+                0 => edge.side_block = p.value as BlockId,
+                1 => edge.edge_block = p.value as BlockId,
+                2 => edge.edge_height = Some(p.value),
+                9 => edge.sides_offset = p.value,
+                // Clouds, view distance, weather speed and fog are not drawn.
+                _ => (),
+            }
+        },
+        ServerPlayPacket::ClickDistance(p) => {
+            game.click_distance = p.distance as f32 / 32.;
+            log::info!("Click distance set to {} blocks", game.click_distance);
+        },
+        ServerPlayPacket::ChangeModel(p) => {
+            // Resolve now so unknown names are reported once.
+            game.models.get(&p.model_name);
+            game.appearances.entry(p.entity_id).or_default().model = p.model_name;
+        },
+        ServerPlayPacket::SetEntityProperty(p) => {
+            game.appearances.entry(p.entity_id).or_default().set_property(p.property_type, p.value);
+        },
+        ServerPlayPacket::HoldThis(p) => {
+            game.inventory.hold_this(p.block_to_hold, p.prevent_change != 0);
+        },
+        ServerPlayPacket::SetHotbar(p) => {
+            game.inventory.set_hotbar(p.hotbar_index as usize, p.block_id);
+        },
+        ServerPlayPacket::InventoryOrder(p) => {
+            game.inventory.set_order(p.block, p.order as usize);
+        },
+        ServerPlayPacket::BulkBlockUpdate(p) => {
+            let count = p.count as usize + 1;
+            let blocks = p.indices.0[..count].iter().zip(&p.blocks.0[..count])
+                .filter_map(|(&index, &id)| {
+                    let position = game.world.index_to_position(usize::try_from(index).ok()?)?;
+                    Some(Block::new(id, position))
+                })
+                .collect::<Vec<Block>>();
+            game.set_blocks(blocks);
         },
     }
     Ok(())
@@ -1,18 +1,19 @@
 use std::io::{Cursor, Write, Read};
 
 use anyhow::bail;
-use flate2::read::GzDecoder;
+use flate2::read::{GzDecoder, DeflateDecoder};
 
-use crate::game::{network::client::packet::ServerWorldPacket, world::World};
+use crate::game::{network::client::packet::{ServerWorldPacket, ServerFastMapPacket}, world::{World, strip_length_prefix}};
 
-use super::{worker::ClientWorker, packet::{ClientPlayPacket, PlayerIdentification, ServerLoginPacket}};
+use super::{worker::ClientWorker, packet::{ClientPlayPacket, PlayerIdentification, ServerLoginPacket, ExtInfo, ExtEntry}, extensions::{ExtensionSet, APP_NAME, CPE_MAGIC, SUPPORTED_EXTENSIONS}};
 
 pub struct ServerDataPackage {
     pub world: World,
+    pub extensions: ExtensionSet,
 }
 impl ServerDataPackage {
-    pub fn new(world: World) -> Self {
-        Self { world }
+    pub fn new(world: World, extensions: ExtensionSet) -> Self {
+        Self { world, extensions }
     }
 }
 
@@ -21,22 +22,67 @@ pub async fn do_handshake(worker: &mut ClientWorker) -> anyhow::Result<ServerDat
         protocol_version: 23,
         username: worker.username.clone(),
         verification_key: String::from(""),
-        unused: 0,
+        unused: CPE_MAGIC,
     })).await?;
-    let p = worker.read::<ServerLoginPacket>().await?;
-    log::info!("P {:?}", p);
+    let mut extensions = ExtensionSet::default();
+    loop {
+        match worker.read::<ServerLoginPacket>().await? {
+            ServerLoginPacket::ExtInfo(info) => {
+                log::info!("Server {} supports {} extensions", info.app_name, info.extension_count);
+                for _ in 0..info.extension_count {
+                    if let ServerLoginPacket::ExtEntry(entry) = worker.read::<ServerLoginPacket>().await? {
+                        extensions.negotiate(&entry.ext_name, entry.version);
+                    } else {
+                        bail!("Expected ExtEntry");
+                    }
+                }
+                worker.write(ClientPlayPacket::ExtInfo(ExtInfo {
+                    app_name: APP_NAME.to_string(),
+                    extension_count: SUPPORTED_EXTENSIONS.len() as i16,
+                })).await?;
+                for (name, version) in SUPPORTED_EXTENSIONS {
+                    worker.write(ClientPlayPacket::ExtEntry(ExtEntry {
+                        ext_name: name.to_string(),
+                        version: *version,
+                    })).await?;
+                }
+            },
+            ServerLoginPacket::ExtEntry(_) => bail!("Unexpected ExtEntry"),
+            p @ ServerLoginPacket::ServerIdentification(_) => {
+                log::info!("P {:?}", p);
+                break;
+            },
+        }
+    }
 
+    let fast_map = extensions.has("FastMap");
+    let extended_blocks = extensions.has("ExtendedBlocks");
+    worker.set_extended_blocks(extended_blocks);
     let mut world_stage_buf = Vec::new();
+    // Upper bits of block IDs under ExtendedBlocks, flagged by any
+    // non-zero `percent_complete`, as ClassiCube reads it. The
+    // lower array is sent with 0, as it doesn't report progress.
+    let mut world_stage_buf_upper = Vec::new();
     let mut world_size_x: i16;
     let mut world_size_y: i16;
     let mut world_size_z: i16;
     loop  {
-        if let Ok(p) = worker.read::<ServerWorldPacket>().await {
+        let p = if fast_map {
+            worker.read::<ServerFastMapPacket>().await.map(ServerWorldPacket::from)
+        } else {
+            worker.read::<ServerWorldPacket>().await
+        };
+        if let Ok(p) = p {
             match p {
                 ServerWorldPacket::LevelInitialize(_) => log::info!("Recieving world"),
                 ServerWorldPacket::LevelDataChunk(data) => {
-                    log::info!("Recieved world chunk, percent {}", data.percent_complete);
-                    world_stage_buf.extend_from_slice(&data.chunk_data.0[..data.chunk_length as usize]);
+                    let chunk = &data.chunk_data.0[..data.chunk_length as usize];
+                    if extended_blocks && data.percent_complete != 0 {
+                        world_stage_buf_upper.extend_from_slice(chunk);
+                    } else {
+                        log::info!("Recieved world chunk, percent {}", data.percent_complete);
+                        world_stage_buf.extend_from_slice(chunk);
+                    }
                 },
                 ServerWorldPacket::LevelFinalize(data) => {
                     world_size_x = data.x_size;
@@ -50,10 +96,28 @@ pub async fn do_handshake(worker: &mut ClientWorker) -> anyhow::Result<ServerDat
             bail!("Bad world")
         }
     }
-    let cursor = Cursor::new(world_stage_buf);
-    let mut cursor = GzDecoder::new(cursor);
+    let output = decompress_level(world_stage_buf, fast_map)?;
+    let upper = if world_stage_buf_upper.is_empty() {
+        None
+    } else {
+        Some(decompress_level(world_stage_buf_upper, fast_map)?)
+    };
+    let world = World::from_data(output, upper, world_size_x, world_size_y, world_size_z)?;
+    Ok(ServerDataPackage::new(world, extensions))
+}
+
+/// Decompresses a downloaded block array, returning
+/// it without the big-endian block count prefix.
+fn decompress_level(data: Vec<u8>, fast_map: bool) -> anyhow::Result<Vec<u8>> {
+    let cursor = Cursor::new(data);
     let mut output = Vec::new();
-    cursor.read_to_end(&mut output)?;
-    let world = World::from_data(output, world_size_x, world_size_y, world_size_z);
-    Ok(ServerDataPackage::new(world))
-}
\ No newline at end of file
+    if fast_map {
+        // FastMap sends raw DEFLATE without the gzip
+        // header or the block count prefix.
+        DeflateDecoder::new(cursor).read_to_end(&mut output)?;
+        Ok(output)
+    } else {
+        GzDecoder::new(cursor).read_to_end(&mut output)?;
+        Ok(strip_length_prefix(&output)?.to_vec())
+    }
+}
@@ -5,15 +5,28 @@ use tokio::net::TcpStream;
 
 use super::{Readable, Writeable};
 
-use self::{worker::ClientWorker, packet::{ServerPlayPacket, ClientPlayPacket, PositionAndOrientation}, handshake::ServerDataPackage};
+use glam::{vec3, Vec3};
+
+use crate::game::{world::{BlockPosition, Facing, BlockId}, player::NETWORK_Y_OFFSET, camera::angle_to_byte};
+
+use self::{worker::ClientWorker, packet::{ServerPlayPacket, ClientPlayPacket, PositionAndOrientation, PlayerClick, PlayerSetBlock}, handshake::ServerDataPackage, extensions::ExtensionSet};
 pub mod worker;
+pub mod extensions;
 pub mod handshake;
 pub mod packet;
 pub mod handle;
+/// What a `PlayerClick` was aimed at.
+#[derive(Default, Clone, Copy)]
+pub struct ClickTarget {
+    pub entity: Option<u8>,
+    pub block: Option<(BlockPosition, Facing)>,
+}
+
 pub struct Client {
     pub reader: Receiver<ServerPlayPacket>,
     pub sender: Sender<ClientPlayPacket>,
     pub username: String,
+    pub extensions: ExtensionSet,
 }
 impl Client {
     pub async fn connect(addr: &str, username: String) -> anyhow::Result<(Self, ServerDataPackage)> {
@@ -22,7 +35,8 @@ impl Client {
         Ok((Self {
             reader: reciever,
             sender,
-            username
+            username,
+            extensions: package.extensions.clone(),
         }, package))
     }
     pub async fn read(&mut self) -> anyhow::Result<ServerPlayPacket> {
@@ -31,14 +45,50 @@ impl Client {
     pub fn write(&mut self, p: ClientPlayPacket) -> anyhow::Result<()> {
         Ok(self.sender.send(p)?)
     }
-    pub fn update_position(&mut self, x: f32, y: f32, z: f32, yaw: u8, pitch: u8) {
+    /// Sends our feet position, in blocks, and Classic orientation. With
+    /// HeldBlock the player ID is replaced by the block we are holding.
+    pub fn update_position(&mut self, position: Vec3, yaw: f32, pitch: f32, held_block: BlockId) {
+        let position = (position + vec3(0., NETWORK_Y_OFFSET, 0.)) * 32.;
         self.write(ClientPlayPacket::PositionAndOrientation(PositionAndOrientation {
-            player_id: 255,
-            x: ((x * 2.) * 32.) as i16,
-            y: ((y * 2.) * 32.) as i16,
-            z: ((z * 2.) * 32.) as i16,
-            yaw,
-            pitch,
+            player_id: if self.extensions.has("HeldBlock") { held_block } else { 255 },
+            x: position.x as i16,
+            y: position.y as i16,
+            z: position.z as i16,
+            yaw: angle_to_byte(yaw),
+            pitch: angle_to_byte(pitch),
         })).unwrap();
     }
-}
\ No newline at end of file
+    /// Tells the server about a mouse click, if it supports PlayerClick.
+    /// `button` is 0 for left, 1 for right and 2 for middle.
+    pub fn player_click(&mut self, button: u8, pressed: bool, yaw: f32, pitch: f32, target: ClickTarget) {
+        if !self.extensions.has("PlayerClick") {
+            return;
+        }
+        let (position, face) = match target.block {
+            Some((p, f)) => (p, f.click_face()),
+            None => (BlockPosition::new(-1, -1, -1), 255),
+        };
+        self.write(ClientPlayPacket::PlayerClick(PlayerClick {
+            button,
+            action: if pressed { 0 } else { 1 },
+            yaw: (yaw.rem_euclid(360.) * 65536. / 360.) as i32 as i16,
+            pitch: (pitch.rem_euclid(360.) * 65536. / 360.) as i32 as i16,
+            target_entity: target.entity.unwrap_or(255),
+            target_x: position.x as i16,
+            target_y: position.y as i16,
+            target_z: position.z as i16,
+            target_face: face,
+        })).unwrap();
+    }
+    /// Asks the server to place `block` at `position`, or
+    /// to delete the block there if `place` is false.
+    pub fn set_block(&mut self, position: BlockPosition, place: bool, block: BlockId) {
+        self.write(ClientPlayPacket::PlayerSetBlock(PlayerSetBlock {
+            x: position.x as i16,
+            y: position.y as i16,
+            z: position.z as i16,
+            mode: place as u8,
+            block_type: block,
+        })).unwrap();
+    }
+}
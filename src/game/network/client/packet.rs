@@ -1,4 +1,4 @@
-use crate::{packets, packet_enum, game::network::io::ByteArray};
+use crate::{packets, packet_enum, game::network::io::{ByteArray, BulkIndexArray, BulkBlockArray, BlockRaw}};
 
 packets! {
     PlayerIdentification {
@@ -7,14 +7,40 @@ packets! {
         verification_key String;
         unused u8;
     }
+    PlayerSetBlock {
+        x i16;
+        y i16;
+        z i16;
+        mode u8;
+        block_type BlockRaw;
+    }
     PositionAndOrientation {
-        player_id u8;
+        player_id BlockRaw;
         x i16;
         y i16;
         z i16;
         yaw u8;
         pitch u8;
     }
+    ExtInfo {
+        app_name String;
+        extension_count i16;
+    }
+    ExtEntry {
+        ext_name String;
+        version i32;
+    }
+    PlayerClick {
+        button u8;
+        action u8;
+        yaw i16;
+        pitch i16;
+        target_entity u8;
+        target_x i16;
+        target_y i16;
+        target_z i16;
+        target_face u8;
+    }
 }
 
 packets! {
@@ -26,6 +52,9 @@ packets! {
     }
     LevelInitialize {
         
+    }
+    LevelInitializeFast {
+        map_size i32;
     }
     Ping {
 
@@ -61,17 +90,171 @@ packets! {
         yaw i8;
         pitch i8;
     }
+    PositionAndOrientationUpdate {
+        player_id i8;
+        dx i8;
+        dy i8;
+        dz i8;
+        yaw u8;
+        pitch u8;
+    }
+    PositionUpdate {
+        player_id i8;
+        dx i8;
+        dy i8;
+        dz i8;
+    }
+    OrientationUpdate {
+        player_id i8;
+        yaw u8;
+        pitch u8;
+    }
+    DespawnPlayer {
+        player_id i8;
+    }
     SetBlock {
         x i16;
         y i16;
         z i16;
-        block_type u8;
+        block_type BlockRaw;
+    }
+    MakeSelection {
+        selection_id u8;
+        label String;
+        start_x i16;
+        start_y i16;
+        start_z i16;
+        end_x i16;
+        end_y i16;
+        end_z i16;
+        red i16;
+        green i16;
+        blue i16;
+        alpha i16;
+    }
+    RemoveSelection {
+        selection_id u8;
+    }
+    ClickDistance {
+        distance i16;
+    }
+    ChangeModel {
+        entity_id u8;
+        model_name String;
+    }
+    SetEntityProperty {
+        entity_id u8;
+        property_type u8;
+        value i32;
+    }
+    SetSpawnpoint {
+        x i16;
+        y i16;
+        z i16;
+        yaw u8;
+        pitch u8;
+    }
+    VelocityControl {
+        x i32;
+        y i32;
+        z i32;
+        x_mode u8;
+        y_mode u8;
+        z_mode u8;
+    }
+    ExtEntityTeleport {
+        entity_id u8;
+        behavior u8;
+        x i16;
+        y i16;
+        z i16;
+        yaw u8;
+        pitch u8;
+    }
+    DefineEffect {
+        effect_id u8;
+        u1 u8;
+        v1 u8;
+        u2 u8;
+        v2 u8;
+        red u8;
+        green u8;
+        blue u8;
+        frame_count u8;
+        particle_count u8;
+        size u8;
+        size_variation i32;
+        spread u16;
+        speed i32;
+        gravity i32;
+        base_lifetime i32;
+        lifetime_variation i32;
+        collide_flags u8;
+        full_bright u8;
+    }
+    SpawnEffect {
+        effect_id u8;
+        x i32;
+        y i32;
+        z i32;
+        origin_x i32;
+        origin_y i32;
+        origin_z i32;
+    }
+    SetTextColor {
+        red u8;
+        green u8;
+        blue u8;
+        alpha u8;
+        code u8;
+    }
+    EnvSetWeatherType {
+        weather_type u8;
+    }
+    SetBlockPermission {
+        block_type BlockRaw;
+        allow_placement u8;
+        allow_deletion u8;
+    }
+    EnvMapAppearance {
+        texture_url String;
+        side_block u8;
+        edge_block u8;
+        side_level i16;
+    }
+    SetMapEnvUrl {
+        texture_url String;
+    }
+    SetMapEnvProperty {
+        property u8;
+        value i32;
+    }
+    HoldThis {
+        block_to_hold BlockRaw;
+        prevent_change u8;
+    }
+    SetHotbar {
+        block_id BlockRaw;
+        hotbar_index u8;
+    }
+    InventoryOrder {
+        block BlockRaw;
+        order BlockRaw;
+    }
+    BulkBlockUpdate {
+        count u8;
+        indices BulkIndexArray;
+        blocks BulkBlockArray;
     }
 }
 
 packet_enum!(ClientPlayPacket {
     0x00 = PlayerIdentification,
+    0x05 = PlayerSetBlock,
     0x08 = PositionAndOrientation,
+    0x10 = ExtInfo,
+    0x11 = ExtEntry,
+    0x22 = PlayerClick,
 });
 
 packet_enum!(ServerPlayPacket {
@@ -79,15 +262,57 @@ packet_enum!(ServerPlayPacket {
     0x0D = Message,
     0x07 = SpawnPlayer,
     0x08 = PlayerTeleport,
-    0x06 = SetBlock
+    0x09 = PositionAndOrientationUpdate,
+    0x0A = PositionUpdate,
+    0x0B = OrientationUpdate,
+    0x0C = DespawnPlayer,
+    0x06 = SetBlock,
+    0x1A = MakeSelection,
+    0x12 = ClickDistance,
+    0x14 = HoldThis,
+    0x2C = InventoryOrder,
+    0x2D = SetHotbar,
+    0x2E = SetSpawnpoint,
+    0x2F = VelocityControl,
+    0x36 = ExtEntityTeleport,
+    0x30 = DefineEffect,
+    0x31 = SpawnEffect,
+    0x27 = SetTextColor,
+    0x1F = EnvSetWeatherType,
+    0x1C = SetBlockPermission,
+    0x1E = EnvMapAppearance,
+    0x28 = SetMapEnvUrl,
+    0x29 = SetMapEnvProperty,
+    0x1B = RemoveSelection,
+    0x1D = ChangeModel,
+    0x2A = SetEntityProperty,
+    0x26 = BulkBlockUpdate,
 });
 
 packet_enum!(ServerLoginPacket {
     0x00 = ServerIdentification,
+    0x10 = ExtInfo,
+    0x11 = ExtEntry,
 });
 
 packet_enum!(ServerWorldPacket {
     0x02 = LevelInitialize,
     0x03 = LevelDataChunk,
     0x04 = LevelFinalize
-});
\ No newline at end of file
+});
+
+// Sent instead of `ServerWorldPacket` when FastMap is negotiated.
+packet_enum!(ServerFastMapPacket {
+    0x02 = LevelInitializeFast,
+    0x03 = LevelDataChunk,
+    0x04 = LevelFinalize
+});
+impl From<ServerFastMapPacket> for ServerWorldPacket {
+    fn from(p: ServerFastMapPacket) -> Self {
+        match p {
+            ServerFastMapPacket::LevelInitializeFast(_) => ServerWorldPacket::LevelInitialize(LevelInitialize {}),
+            ServerFastMapPacket::LevelDataChunk(p) => ServerWorldPacket::LevelDataChunk(p),
+            ServerFastMapPacket::LevelFinalize(p) => ServerWorldPacket::LevelFinalize(p),
+        }
+    }
+}
@@ -58,6 +58,13 @@ impl ClientWorker {
         self.writer.write(packet).await
     }
 
+    /// Switches both directions to two byte block IDs
+    /// once ExtendedBlocks is negotiated.
+    pub fn set_extended_blocks(&mut self, state: bool) {
+        self.reader.codec.set_extended_blocks(state);
+        self.writer.codec.set_extended_blocks(state);
+    }
+
     pub async fn split(mut self) -> (Sender<ClientPlayPacket>, Receiver<ServerPlayPacket>, ServerDataPackage) {
         let p = super::handshake::do_handshake(&mut self).await.unwrap();
         let Self {
@@ -1,6 +1,6 @@
 use super::io::VarInt;
 
-use super::{Readable, Writeable};
+use super::{io::with_extended_blocks, Readable, Writeable};
 use bytes::BytesMut;
 use std::io::{Cursor, Read};
 
@@ -11,6 +11,8 @@ pub struct Codec {
     received_buf: BytesMut,
     /// Auxilary buffer.
     staging_buf: Vec<u8>,
+    /// Whether block IDs are two bytes, under ExtendedBlocks.
+    extended_blocks: bool,
 }
 
 impl Codec {
@@ -24,12 +26,18 @@ impl Codec {
         Codec {
             received_buf: BytesMut::new(),
             staging_buf: Vec::new(),
+            extended_blocks: self.extended_blocks,
         }
     }
 
+    /// Switches the width of block IDs, once ExtendedBlocks is negotiated.
+    pub fn set_extended_blocks(&mut self, state: bool) {
+        self.extended_blocks = state;
+    }
+
     /// Writes a packet into the provided writer.
     pub fn encode(&mut self, packet: &impl Writeable, output: &mut Vec<u8>) -> anyhow::Result<()> {
-        packet.write(&mut self.staging_buf)?;
+        with_extended_blocks(self.extended_blocks, || packet.write(&mut self.staging_buf))?;
         self.encode_uncompressed(output)?;
         self.staging_buf.clear();
 
@@ -62,7 +70,7 @@ impl Codec {
     {
         let mut cursor = Cursor::new(&self.received_buf[..]);
         let start = cursor.position();
-        let packet = T::read(&mut cursor);
+        let packet = with_extended_blocks(self.extended_blocks, || T::read(&mut cursor));
         if packet.is_err() {
             return Ok(None);
         }
@@ -73,3 +81,46 @@ impl Codec {
         Ok(Some(packet))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::network::client::packet::ServerPlayPacket;
+
+    #[test]
+    fn reads_past_set_map_env_url() {
+        let mut codec = Codec::new();
+        let mut url = b"http://example.com/pack.zip".to_vec();
+        url.resize(64, b' ');
+        codec.accept(&[0x28]);
+        codec.accept(&url);
+        codec.accept(&[0x01]);
+        match codec.next_packet::<ServerPlayPacket>().unwrap() {
+            Some(ServerPlayPacket::SetMapEnvUrl(p)) => assert_eq!(p.texture_url, "http://example.com/pack.zip"),
+            _ => panic!("expected SetMapEnvUrl"),
+        }
+        assert!(matches!(codec.next_packet::<ServerPlayPacket>().unwrap(), Some(ServerPlayPacket::Ping(_))));
+        assert!(codec.next_packet::<ServerPlayPacket>().unwrap().is_none());
+    }
+
+    #[test]
+    fn block_width_is_per_codec() {
+        let set_block = [0x06, 0, 1, 0, 2, 0, 3, 0x01, 0x02];
+        let mut extended = Codec::new();
+        extended.set_extended_blocks(true);
+        let mut classic = Codec::new();
+        extended.accept(&set_block);
+        classic.accept(&set_block);
+        match extended.next_packet::<ServerPlayPacket>().unwrap() {
+            Some(ServerPlayPacket::SetBlock(p)) => assert_eq!(p.block_type, 0x0102),
+            _ => panic!("expected SetBlock"),
+        }
+        match classic.next_packet::<ServerPlayPacket>().unwrap() {
+            Some(ServerPlayPacket::SetBlock(p)) => assert_eq!(p.block_type, 0x01),
+            _ => panic!("expected SetBlock"),
+        }
+        // Only the classic codec leaves the second byte behind.
+        assert_eq!(extended.received_buf.len(), 0);
+        assert_eq!(classic.received_buf.len(), 1);
+    }
+}
@@ -12,6 +12,7 @@ use std::{
     io::{self, Cursor, Read, Write},
     iter::{self, FromIterator},
     marker::PhantomData,
+    cell::Cell,
     num::TryFromIntError,
 };
 use thiserror::Error;
@@ -288,20 +289,131 @@ impl Writeable for bool {
     }
 }
 #[derive(Clone, Debug)]
-pub struct ByteArray(pub [u8; 1024]);
+pub struct ByteArray(pub Box<[u8; 1024]>);
 impl Readable for ByteArray {
     fn read(buffer: &mut Cursor<&[u8]>) -> anyhow::Result<Self>
     where
         Self: Sized,
     {
-        let mut arr = [0; 1024];
-        buffer.read_exact(&mut arr)?;
+        let mut arr = Box::new([0; 1024]);
+        buffer.read_exact(&mut *arr)?;
         Ok(Self(arr))
     }
 }
 impl Writeable for ByteArray {
     fn write(&self, buffer: &mut Vec<u8>) -> anyhow::Result<()> {
-        buffer.extend_from_slice(&self.0);
+        buffer.extend_from_slice(&*self.0);
+        Ok(())
+    }
+}
+thread_local! {
+    /// Whether the packet being read or written uses ExtendedBlocks,
+    /// which sends block IDs as two bytes instead of one.
+    static EXTENDED_BLOCKS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` with block IDs `state` bytes wide. The setting lives in
+/// each `Codec`, which passes it down this way as `Readable` and
+/// `Writeable` only see the buffer.
+pub fn with_extended_blocks<R>(state: bool, f: impl FnOnce() -> R) -> R {
+    let previous = EXTENDED_BLOCKS.with(|extended| extended.replace(state));
+    let result = f();
+    EXTENDED_BLOCKS.with(|extended| extended.set(previous));
+    result
+}
+
+fn extended_blocks() -> bool {
+    EXTENDED_BLOCKS.with(Cell::get)
+}
+
+/// A block ID, one byte wide unless ExtendedBlocks is in use.
+#[derive(Copy, Clone, Debug)]
+pub struct BlockRaw(pub u16);
+impl Readable for BlockRaw {
+    fn read(buffer: &mut Cursor<&[u8]>) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        if extended_blocks() {
+            Ok(Self(u16::read(buffer)?))
+        } else {
+            Ok(Self(u8::read(buffer)? as u16))
+        }
+    }
+}
+impl Writeable for BlockRaw {
+    fn write(&self, buffer: &mut Vec<u8>) -> anyhow::Result<()> {
+        if extended_blocks() {
+            self.0.write(buffer)
+        } else {
+            (self.0 as u8).write(buffer)
+        }
+    }
+}
+impl From<BlockRaw> for u16 {
+    fn from(x: BlockRaw) -> Self {
+        x.0
+    }
+}
+
+/// Block indices of a `BulkBlockUpdate`.
+#[derive(Clone, Debug)]
+pub struct BulkIndexArray(pub Box<[i32; 256]>);
+impl Readable for BulkIndexArray {
+    fn read(buffer: &mut Cursor<&[u8]>) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut arr = Box::new([0; 256]);
+        buffer.read_i32_into::<BigEndian>(&mut *arr)?;
+        Ok(Self(arr))
+    }
+}
+impl Writeable for BulkIndexArray {
+    fn write(&self, buffer: &mut Vec<u8>) -> anyhow::Result<()> {
+        for index in self.0.iter() {
+            index.write(buffer)?;
+        }
+        Ok(())
+    }
+}
+/// Block types of a `BulkBlockUpdate`. With ExtendedBlocks,
+/// the lower bytes are followed by 64 bytes packing the upper
+/// 2 bits of four blocks each.
+#[derive(Clone, Debug)]
+pub struct BulkBlockArray(pub Box<[u16; 256]>);
+impl Readable for BulkBlockArray {
+    fn read(buffer: &mut Cursor<&[u8]>) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut lower = [0; 256];
+        buffer.read_exact(&mut lower)?;
+        let mut arr = Box::new([0; 256]);
+        for (block, lower) in arr.iter_mut().zip(lower) {
+            *block = lower as u16;
+        }
+        if extended_blocks() {
+            let mut upper = [0; 64];
+            buffer.read_exact(&mut upper)?;
+            for (i, block) in arr.iter_mut().enumerate() {
+                let bits = (upper[i >> 2] >> ((i & 3) * 2)) & 0x03;
+                *block |= (bits as u16) << 8;
+            }
+        }
+        Ok(Self(arr))
+    }
+}
+impl Writeable for BulkBlockArray {
+    fn write(&self, buffer: &mut Vec<u8>) -> anyhow::Result<()> {
+        buffer.extend(self.0.iter().map(|&b| b as u8));
+        if extended_blocks() {
+            let mut upper = [0u8; 64];
+            for (i, block) in self.0.iter().enumerate() {
+                upper[i >> 2] |= (((block >> 8) & 0x03) as u8) << ((i & 3) * 2);
+            }
+            buffer.extend_from_slice(&upper);
+        }
         Ok(())
     }
 }
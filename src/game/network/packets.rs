@@ -12,6 +12,9 @@ macro_rules! user_type {
     (Angle) => {
         f32
     };
+    (BlockRaw) => {
+        u16
+    };
     ($typ:ty) => {
         $typ
     };
@@ -27,6 +30,9 @@ macro_rules! user_type_convert_to_writeable {
     (LengthInferredVecU8, $e:expr) => {
         LengthInferredVecU8::from($e.as_slice())
     };
+    (BlockRaw, $e:expr) => {
+        $crate::game::network::io::BlockRaw(*$e)
+    };
     ($typ:ty, $e:expr) => {
         $e
     };
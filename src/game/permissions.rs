@@ -0,0 +1,39 @@
+use super::world::BlockId;
+
+/// Number of block IDs ExtendedBlocks can address.
+const BLOCK_COUNT: usize = 1024;
+
+/// Which blocks the player may place and delete. Starts with
+/// Classic's rules; servers change it with SetBlockPermission.
+pub struct BlockPermissions {
+    place: Box<[bool]>,
+    delete: Box<[bool]>,
+}
+impl Default for BlockPermissions {
+    fn default() -> Self {
+        let mut permissions = Self {
+            place: vec![true; BLOCK_COUNT].into_boxed_slice(),
+            delete: vec![true; BLOCK_COUNT].into_boxed_slice(),
+        };
+        // Air, bedrock, water, still water, lava and still lava.
+        for block in [0, 7, 8, 9, 10, 11] {
+            permissions.set(block, false, false);
+        }
+        permissions
+    }
+}
+impl BlockPermissions {
+    /// Handles SetBlockPermission.
+    pub fn set(&mut self, block: BlockId, place: bool, delete: bool) {
+        if let (Some(p), Some(d)) = (self.place.get_mut(block as usize), self.delete.get_mut(block as usize)) {
+            *p = place;
+            *d = delete;
+        }
+    }
+    pub fn can_place(&self, block: BlockId) -> bool {
+        self.place.get(block as usize).copied().unwrap_or(false)
+    }
+    pub fn can_delete(&self, block: BlockId) -> bool {
+        self.delete.get(block as usize).copied().unwrap_or(false)
+    }
+}
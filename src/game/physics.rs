@@ -0,0 +1,136 @@
+//! Axis-aligned boxes and moving them through a `World`
+//! without passing into solid blocks.
+
+use glam::{vec3, Vec3};
+
+use super::world::{block_collision, BlockCollision, BlockId, BlockPosition, World};
+
+/// An axis-aligned bounding box, in blocks.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+    /// A box `width` wide and `height` tall, standing centred on `feet`.
+    pub fn from_feet(feet: Vec3, width: f32, height: f32) -> Self {
+        let half = width / 2.;
+        Self::new(feet - vec3(half, 0., half), feet + vec3(half, height, half))
+    }
+    pub fn translate(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+    /// Grows the box to cover everywhere it passes through when moved by `motion`.
+    pub fn expand(&self, motion: Vec3) -> Self {
+        Self::new(self.min + motion.min(Vec3::ZERO), self.max + motion.max(Vec3::ZERO))
+    }
+    /// Whether the boxes overlap, not counting touching faces.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis])
+    }
+    /// Shortens a move of `distance` along `axis` so this box
+    /// stops against `other`, if it would otherwise pass into it.
+    fn clip(&self, other: &Aabb, axis: usize, distance: f32) -> f32 {
+        let overlaps = (0..3)
+            .filter(|&a| a != axis)
+            .all(|a| self.min[a] < other.max[a] && self.max[a] > other.min[a]);
+        if !overlaps {
+            distance
+        } else if distance > 0. && self.max[axis] <= other.min[axis] {
+            distance.min(other.min[axis] - self.max[axis])
+        } else if distance < 0. && self.min[axis] >= other.max[axis] {
+            distance.max(other.max[axis] - self.min[axis])
+        } else {
+            distance
+        }
+    }
+}
+
+/// The space taken by a full block at `position`.
+pub fn block_bounds(position: BlockPosition) -> Aabb {
+    let min = vec3(position.x as f32, position.y as f32, position.z as f32);
+    Aabb::new(min, min + Vec3::ONE)
+}
+
+/// Height of a block's collision box, for blocks not a full cube tall.
+fn block_height(id: BlockId) -> f32 {
+    match id {
+        44 | 50 => 0.5,
+        _ => 1.,
+    }
+}
+
+/// Blocks overlapping `area`, with their positions.
+fn blocks_in<'a>(world: &'a World, area: &Aabb) -> impl Iterator<Item = (BlockPosition, BlockId)> + 'a {
+    let min = area.min.floor();
+    let max = area.max.ceil();
+    let (x0, y0, z0) = (min.x as i32, min.y as i32, min.z as i32);
+    let (x1, y1, z1) = (max.x as i32, max.y as i32, max.z as i32);
+    (y0..y1).flat_map(move |y| (z0..z1).flat_map(move |z| (x0..x1).map(move |x| BlockPosition::new(x, y, z))))
+        .map(move |position| (position, world.get_block(position)))
+}
+
+/// Collision boxes of the solid blocks overlapping `area`. Below the
+/// world counts as solid so nothing falls out of it.
+pub fn solid_boxes(world: &World, area: &Aabb) -> Vec<Aabb> {
+    blocks_in(world, area)
+        .filter(|&(position, id)| position.y < 0 || block_collision(id) == BlockCollision::Solid)
+        .map(|(position, id)| {
+            let mut bounds = block_bounds(position);
+            if position.y >= 0 {
+                bounds.max.y -= 1. - block_height(id);
+            }
+            bounds
+        })
+        .filter(|block| block.intersects(area))
+        .collect()
+}
+
+/// Whether any block overlapping `bounds` matches `filter`.
+pub fn touches(world: &World, bounds: &Aabb, filter: impl Fn(BlockId) -> bool) -> bool {
+    blocks_in(world, bounds).any(|(_, id)| filter(id))
+}
+
+/// Moves `bounds` by `motion`, one axis at a time starting with Y,
+/// stopping against solid blocks. Returns how far it actually moved.
+pub fn move_and_collide(world: &World, bounds: Aabb, motion: Vec3) -> Vec3 {
+    let solids = solid_boxes(world, &bounds.expand(motion));
+    let mut bounds = bounds;
+    let mut moved = Vec3::ZERO;
+    for axis in [1, 0, 2] {
+        let distance = solids.iter().fold(motion[axis], |distance, solid| bounds.clip(solid, axis, distance));
+        let mut offset = Vec3::ZERO;
+        offset[axis] = distance;
+        bounds = bounds.translate(offset);
+        moved[axis] = distance;
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::FlatWorldGenerator;
+
+    #[test]
+    fn stops_on_the_ground() {
+        // Ground at Y 0 and 1, so the top surface is at 2.
+        let world = World::new(FlatWorldGenerator::new(2, 1, 2, 0), 8, 8, 8);
+        let bounds = Aabb::from_feet(vec3(4., 3., 4.), 0.6, 1.8);
+        assert_eq!(move_and_collide(&world, bounds, vec3(0., -5., 0.)), vec3(0., -1., 0.));
+        assert_eq!(move_and_collide(&world, bounds, vec3(0.5, 0., 0.)), vec3(0.5, 0., 0.));
+    }
+
+    #[test]
+    fn walls_stop_each_axis_separately() {
+        let mut world = World::new(FlatWorldGenerator::new(2, 1, 2, 0), 8, 8, 8);
+        world.set(BlockPosition::new(5, 2, 4), 1).unwrap();
+        let bounds = Aabb::from_feet(vec3(4.5, 2., 4.5), 0.6, 1.8);
+        let moved = move_and_collide(&world, bounds, vec3(1., 0., 0.25));
+        assert!((moved.x - 0.2).abs() < 1e-5);
+        assert_eq!(moved.z, 0.25);
+    }
+}
@@ -0,0 +1,347 @@
+use glam::{const_vec3, vec3, Vec3};
+
+use super::{physics::{self, Aabb}, world::World};
+
+/// Height of the player's eyes above their feet, in blocks.
+pub const EYE_HEIGHT: f32 = 1.625;
+
+/// Classic positions for the local player are sent
+/// this far above the feet, in blocks.
+pub const NETWORK_Y_OFFSET: f32 = 51. / 32.;
+
+/// Converts a fixed-point position sent for the local
+/// player into feet coordinates, in blocks.
+pub fn from_network_position(x: i32, y: i32, z: i32) -> Vec3 {
+    vec3(x as f32, y as f32, z as f32) / 32. - vec3(0., NETWORK_Y_OFFSET, 0.)
+}
+
+/// Ticks per second. Physics runs at a fixed rate, with
+/// the constants below given per tick as in Classic.
+pub const TICKS_PER_SECOND: f32 = 20.;
+
+/// Size of the player's hitbox, in blocks.
+pub const WIDTH: f32 = 0.6;
+pub const HEIGHT: f32 = 1.8;
+/// Tallest ledge the player walks up without jumping.
+const STEP_HEIGHT: f32 = 0.5;
+const GRAVITY: f32 = 0.08;
+const JUMP_VELOCITY: f32 = 0.42;
+const GROUND_ACCELERATION: f32 = 0.1;
+const AIR_ACCELERATION: f32 = 0.02;
+/// Horizontal and vertical velocity kept per tick in the air.
+const AIR_DRAG: Vec3 = const_vec3!([0.91, 0.98, 0.91]);
+/// Extra horizontal velocity kept per tick on the ground.
+const GROUND_FRICTION: f32 = 0.6;
+const LIQUID_ACCELERATION: f32 = 0.02;
+const LIQUID_GRAVITY: f32 = 0.02;
+const SWIM_VELOCITY: f32 = 0.04;
+const WATER_DRAG: f32 = 0.8;
+const LAVA_DRAG: f32 = 0.5;
+/// Upwards velocity for climbing out of a liquid onto a ledge.
+const CLIMB_OUT_VELOCITY: f32 = 0.3;
+const FLY_SPEED: f32 = 0.5;
+/// Most ticks run in one update, so a long frame can't stall the game.
+const MAX_TICKS_PER_UPDATE: u32 = 10;
+
+/// What the player is trying to do, from the keyboard.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MovementInput {
+    /// Horizontal direction to walk in, no longer than 1.
+    pub direction: Vec3,
+    /// Jump, swim up or fly up.
+    pub jump: bool,
+    /// Fly down.
+    pub descend: bool,
+}
+
+/// Where the player returns to on respawn.
+#[derive(Clone, Copy, Debug)]
+pub struct Spawnpoint {
+    /// Feet position, in blocks.
+    pub position: Vec3,
+    /// Classic yaw and pitch, in degrees.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+impl Default for Spawnpoint {
+    fn default() -> Self {
+        Self { position: Vec3::ZERO, yaw: 0., pitch: 0. }
+    }
+}
+
+/// How a `VelocityControl` component is applied.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VelocityMode {
+    Add,
+    Set,
+}
+impl VelocityMode {
+    pub fn from_byte(mode: u8) -> Self {
+        if mode == 0 { VelocityMode::Add } else { VelocityMode::Set }
+    }
+}
+
+/// Movement state of the local player.
+pub struct Player {
+    /// Feet position, in blocks.
+    pub position: Vec3,
+    /// In blocks per second.
+    pub velocity: Vec3,
+    pub spawn: Spawnpoint,
+    /// Flying ignores gravity and liquids, but not collision.
+    pub flying: bool,
+    pub on_ground: bool,
+    /// Position at the start of the current tick, for smoothing.
+    previous_position: Vec3,
+    /// Seconds since the last tick.
+    tick_time: f32,
+}
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            velocity: Vec3::ZERO,
+            spawn: Spawnpoint::default(),
+            flying: false,
+            on_ground: false,
+            previous_position: Vec3::ZERO,
+            tick_time: 0.,
+        }
+    }
+}
+impl Player {
+    /// Position of the player's eyes, in blocks.
+    pub fn eye_position(&self) -> Vec3 {
+        self.position + vec3(0., EYE_HEIGHT, 0.)
+    }
+    /// Eye position between the last two ticks, for a smooth camera.
+    pub fn smoothed_eye_position(&self) -> Vec3 {
+        let t = self.tick_time * TICKS_PER_SECOND;
+        self.previous_position.lerp(self.position, t) + vec3(0., EYE_HEIGHT, 0.)
+    }
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_feet(self.position, WIDTH, HEIGHT)
+    }
+    /// Moves the player to `position`, stopping them.
+    pub fn teleport(&mut self, position: Vec3) {
+        self.position = position;
+        self.previous_position = position;
+        self.velocity = Vec3::ZERO;
+    }
+    /// Returns the player to their spawnpoint.
+    pub fn respawn(&mut self) -> Spawnpoint {
+        self.teleport(self.spawn.position);
+        self.spawn
+    }
+    /// Applies a VelocityControl. `velocity` is in blocks per tick.
+    pub fn apply_velocity(&mut self, velocity: Vec3, modes: [VelocityMode; 3]) {
+        let velocity = velocity * TICKS_PER_SECOND;
+        for (axis, mode) in modes.into_iter().enumerate() {
+            match mode {
+                VelocityMode::Add => self.velocity[axis] += velocity[axis],
+                VelocityMode::Set => self.velocity[axis] = velocity[axis],
+            }
+        }
+    }
+    /// Advances the player by `delta` seconds, in whole ticks.
+    pub fn update(&mut self, delta: f32, world: &World, input: &MovementInput) {
+        const TICK: f32 = 1. / TICKS_PER_SECOND;
+        self.tick_time += delta;
+        let mut ticks = 0;
+        while self.tick_time >= TICK {
+            self.tick_time -= TICK;
+            if ticks == MAX_TICKS_PER_UPDATE {
+                self.tick_time = 0.;
+                break;
+            }
+            self.previous_position = self.position;
+            self.tick(world, input);
+            ticks += 1;
+        }
+    }
+    /// Runs one tick of physics.
+    pub fn tick(&mut self, world: &World, input: &MovementInput) {
+        let direction = if input.direction.length_squared() > 1. { input.direction.normalize() } else { input.direction };
+        // Velocity in blocks per tick.
+        let mut velocity = self.velocity / TICKS_PER_SECOND;
+        let liquid = if self.flying { None } else { self.liquid(world) };
+        if self.flying {
+            let vertical = input.jump as i32 - input.descend as i32;
+            velocity = (direction + vec3(0., vertical as f32, 0.)) * FLY_SPEED;
+            self.move_by(world, &mut velocity);
+        } else if let Some(drag) = liquid {
+            if input.jump {
+                velocity.y += SWIM_VELOCITY;
+            }
+            velocity += direction * LIQUID_ACCELERATION;
+            let start_y = self.position.y;
+            let wanted = velocity;
+            self.move_by(world, &mut velocity);
+            let blocked = velocity.x != wanted.x || velocity.z != wanted.z;
+            velocity *= drag;
+            velocity.y -= LIQUID_GRAVITY;
+            // Hop out onto the bank when swimming against it.
+            let climb = vec3(velocity.x, velocity.y + 0.6 - (self.position.y - start_y), velocity.z);
+            if blocked && physics::solid_boxes(world, &self.bounds().translate(climb)).is_empty() {
+                velocity.y = CLIMB_OUT_VELOCITY;
+            }
+        } else {
+            if input.jump && self.on_ground {
+                velocity.y = JUMP_VELOCITY;
+            }
+            let acceleration = if self.on_ground { GROUND_ACCELERATION } else { AIR_ACCELERATION };
+            velocity += direction * acceleration;
+            self.move_by(world, &mut velocity);
+            velocity *= AIR_DRAG;
+            velocity.y -= GRAVITY;
+            if self.on_ground {
+                velocity.x *= GROUND_FRICTION;
+                velocity.z *= GROUND_FRICTION;
+            }
+        }
+        if velocity.length_squared() < 1e-8 {
+            velocity = Vec3::ZERO;
+        }
+        self.velocity = velocity * TICKS_PER_SECOND;
+    }
+    /// Drag of the liquid the player is in, if any.
+    fn liquid(&self, world: &World) -> Option<f32> {
+        let bounds = self.bounds();
+        if physics::touches(world, &bounds, |id| id == 10 || id == 11) {
+            Some(LAVA_DRAG)
+        } else if physics::touches(world, &bounds, |id| id == 8 || id == 9) {
+            Some(WATER_DRAG)
+        } else {
+            None
+        }
+    }
+    /// Moves by `velocity`, stepping up low ledges when walking, and
+    /// zeroes the parts of `velocity` stopped by a collision.
+    fn move_by(&mut self, world: &World, velocity: &mut Vec3) {
+        let motion = *velocity;
+        let bounds = self.bounds();
+        let mut moved = physics::move_and_collide(world, bounds, motion);
+        let mut on_ground = motion.y < 0. && moved.y != motion.y;
+        let blocked = moved.x != motion.x || moved.z != motion.z;
+        if blocked && self.on_ground && !self.flying && motion.y <= 0. {
+            // Lift, move across, then drop back down, keeping
+            // that instead if it gets further.
+            let up = physics::move_and_collide(world, bounds, vec3(0., STEP_HEIGHT, 0.));
+            let lifted = bounds.translate(up);
+            let across = physics::move_and_collide(world, lifted, vec3(motion.x, 0., motion.z));
+            let down = physics::move_and_collide(world, lifted.translate(across), vec3(0., -up.y, 0.));
+            if across.x * across.x + across.z * across.z > moved.x * moved.x + moved.z * moved.z {
+                moved = up + across + down;
+                on_ground = true;
+            }
+        }
+        self.position += moved;
+        self.on_ground = on_ground;
+        if moved.x != motion.x {
+            velocity.x = 0.;
+        }
+        if moved.z != motion.z {
+            velocity.z = 0.;
+        }
+        if on_ground || moved.y != motion.y {
+            velocity.y = 0.;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::{BlockPosition, FlatWorldGenerator};
+
+    /// Ground at Y 0 to 3, so the surface is at 4.
+    fn world() -> World {
+        World::new(FlatWorldGenerator::new(4, 1, 2, 0), 16, 16, 16)
+    }
+
+    fn player(position: Vec3) -> Player {
+        let mut player = Player::default();
+        player.teleport(position);
+        player
+    }
+
+    fn run(player: &mut Player, world: &World, input: MovementInput, ticks: usize) {
+        for _ in 0..ticks {
+            player.tick(world, &input);
+        }
+    }
+
+    #[test]
+    fn falls_and_lands() {
+        let world = world();
+        let mut player = player(vec3(8.5, 10., 8.5));
+        run(&mut player, &world, MovementInput::default(), 40);
+        assert_eq!(player.position, vec3(8.5, 4., 8.5));
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn jumps_about_a_block() {
+        let world = world();
+        let mut player = player(vec3(8.5, 4., 8.5));
+        run(&mut player, &world, MovementInput::default(), 2);
+        let jump = MovementInput { jump: true, ..Default::default() };
+        player.tick(&world, &jump);
+        let mut highest: f32 = 0.;
+        for _ in 0..20 {
+            player.tick(&world, &MovementInput::default());
+            highest = highest.max(player.position.y);
+        }
+        assert!(highest > 5.1 && highest < 5.5, "{}", highest);
+        assert_eq!(player.position.y, 4.);
+    }
+
+    #[test]
+    fn walls_block_and_ledges_are_stepped() {
+        let mut world = world();
+        let walk = MovementInput { direction: vec3(1., 0., 0.), ..Default::default() };
+        world.set(BlockPosition::new(10, 4, 8), 1).unwrap();
+        world.set(BlockPosition::new(10, 5, 8), 1).unwrap();
+        let mut player = player(vec3(8.5, 4., 8.5));
+        run(&mut player, &world, walk, 40);
+        assert!((player.position.x - 9.7).abs() < 1e-4);
+        assert_eq!(player.position.y, 4.);
+
+        // A slab is low enough to walk onto.
+        world.set(BlockPosition::new(10, 5, 8), 0).unwrap();
+        world.set(BlockPosition::new(10, 4, 8), 44).unwrap();
+        run(&mut player, &world, walk, 3);
+        assert!(player.position.x > 10. && player.position.x < 11.);
+        assert_eq!(player.position.y, 4.5);
+    }
+
+    #[test]
+    fn water_slows_falling() {
+        let mut world = world();
+        for y in 4..12 {
+            world.set(BlockPosition::new(8, y, 8), 8).unwrap();
+        }
+        let mut dry = player(vec3(3.5, 12., 3.5));
+        let mut wet = player(vec3(8.5, 12., 8.5));
+        run(&mut dry, &world, MovementInput::default(), 12);
+        run(&mut wet, &world, MovementInput::default(), 12);
+        assert!(wet.position.y > dry.position.y + 1.);
+        // Swimming up stops the sinking.
+        let swim = MovementInput { jump: true, ..Default::default() };
+        let y = wet.position.y;
+        run(&mut wet, &world, swim, 20);
+        assert!(wet.position.y > y);
+    }
+
+    #[test]
+    fn flying_ignores_gravity() {
+        let world = world();
+        let mut player = player(vec3(8.5, 10., 8.5));
+        player.flying = true;
+        run(&mut player, &world, MovementInput::default(), 20);
+        assert_eq!(player.position.y, 10.);
+        let descend = MovementInput { descend: true, ..Default::default() };
+        run(&mut player, &world, descend, 40);
+        assert_eq!(player.position.y, 4.);
+    }
+}
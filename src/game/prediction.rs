@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use fnv::FnvHashMap;
+
+use super::world::{Block, BlockId, BlockPosition, World};
+
+/// How long to wait for the server to answer a block change. Many
+/// servers only echo changes they refuse, so silence means accepted.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Block changes made locally and sent to the
+/// server, which it may still contradict.
+#[derive(Default)]
+pub struct PendingBlocks {
+    /// The IDs we set, and when.
+    changes: FnvHashMap<BlockPosition, (BlockId, Instant)>,
+}
+impl PendingBlocks {
+    /// Records a change we made and sent.
+    pub fn insert(&mut self, position: BlockPosition, id: BlockId) {
+        self.insert_at(position, id, Instant::now());
+    }
+    fn insert_at(&mut self, position: BlockPosition, id: BlockId, now: Instant) {
+        self.expire(now);
+        self.changes.insert(position, (id, now));
+    }
+    /// Applies a SetBlock from the server to `world`, unless it only
+    /// confirms our own change. One that disagrees rolls ours back.
+    /// Returns whether the world changed.
+    pub fn apply(&mut self, world: &mut World, block: Block) -> anyhow::Result<bool> {
+        self.apply_at(world, block, Instant::now())
+    }
+    fn apply_at(&mut self, world: &mut World, block: Block, now: Instant) -> anyhow::Result<bool> {
+        self.expire(now);
+        if let Some((id, _)) = self.changes.remove(&block.position) {
+            if id == block.id && world.get(block.position) == Some(id) {
+                return Ok(false);
+            }
+            log::info!("Server rolled back our change at {:?}", block.position);
+        }
+        world.set_block(block)?;
+        Ok(true)
+    }
+    fn expire(&mut self, now: Instant) {
+        self.changes.retain(|_, (_, sent)| now.duration_since(*sent) < TIMEOUT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::FlatWorldGenerator;
+
+    fn world() -> World {
+        World::new(FlatWorldGenerator::new(2, 1, 2, 0), 4, 4, 4)
+    }
+
+    #[test]
+    fn contradicting_set_block_restores_the_server_block() {
+        let mut world = world();
+        let mut pending = PendingBlocks::default();
+        let position = BlockPosition::new(1, 2, 1);
+        world.set(position, 45).unwrap();
+        pending.insert(position, 45);
+        assert!(pending.apply(&mut world, Block::new(0, position)).unwrap());
+        assert_eq!(world.get(position), Some(0));
+    }
+
+    #[test]
+    fn echoes_confirm_and_expire() {
+        let mut world = world();
+        let mut pending = PendingBlocks::default();
+        let position = BlockPosition::new(1, 2, 1);
+        let now = Instant::now();
+        world.set(position, 45).unwrap();
+        pending.insert_at(position, 45, now);
+        assert!(!pending.apply_at(&mut world, Block::new(45, position), now).unwrap());
+        assert!(pending.changes.is_empty());
+
+        // An unanswered change is forgotten, so later changes apply as usual.
+        pending.insert_at(position, 45, now);
+        pending.insert_at(BlockPosition::new(2, 2, 2), 1, now + TIMEOUT);
+        assert_eq!(pending.changes.len(), 1);
+        assert!(pending.apply_at(&mut world, Block::new(3, position), now + TIMEOUT).unwrap());
+        assert_eq!(world.get(position), Some(3));
+    }
+}
@@ -0,0 +1,188 @@
+use std::rc::Rc;
+
+use glam::{vec2, vec3, Vec2, Vec3, Vec4};
+
+use crate::{render::{stage::RenderStage, window::GameWindow, opengl::{vao::VertexArrayObject, buffer::VertexBuffer, program::ShaderProgram}}, game::{CubeGame, texture::TerrainAtlas, world::{World, BlockId, block_to_render}}};
+
+/// How far the planes reach past the world, in blocks.
+const EXTENT: f32 = 1024.;
+/// Walls are pushed out from the world by this much
+/// to avoid z-fighting with its outermost faces.
+const EPSILON: f32 = 1. / 64.;
+
+/// Settings for the area around the map, changed
+/// by EnvMapAppearance and EnvMapAspect.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MapEdge {
+    /// Block on the sides and beneath the map.
+    pub side_block: BlockId,
+    /// Block of the horizon plane.
+    pub edge_block: BlockId,
+    /// Height of the horizon, or `None` for half the world's height.
+    pub edge_height: Option<i32>,
+    /// Sides are drawn this far from the edge height.
+    pub sides_offset: i32,
+}
+impl Default for MapEdge {
+    fn default() -> Self {
+        Self {
+            side_block: 7,
+            edge_block: 8,
+            edge_height: None,
+            sides_offset: -2,
+        }
+    }
+}
+
+/// Draws Classic's surroundings: bedrock sides and
+/// floor, and a water horizon reaching into the distance.
+pub struct EdgeRenderer {
+    pub edge: MapEdge,
+    /// Settings and world size the buffers were built for.
+    built: Option<(MapEdge, usize, usize, usize)>,
+    vao: VertexArrayObject,
+    vertices: VertexBuffer<Vec4>,
+    /// Per vertex: position on the surface in blocks,
+    /// then the atlas tile to repeat across it.
+    texcoords: VertexBuffer<Vec4>,
+    shaders: ShaderProgram,
+    textures: Rc<TerrainAtlas>,
+}
+impl EdgeRenderer {
+    pub fn init(_w: &mut GameWindow, textures: Rc<TerrainAtlas>) -> Self {
+        unsafe {
+            let vao = VertexArrayObject::new();
+            Self {
+                edge: MapEdge::default(),
+                built: None,
+                vao,
+                vertices: VertexBuffer::new(gl::STATIC_DRAW),
+                texcoords: VertexBuffer::new(gl::STATIC_DRAW),
+                shaders: ShaderProgram::new(&[
+                    (gl::VERTEX_SHADER, Self::VERTEX_SHADER),
+                    (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
+                ]),
+                textures,
+            }
+        }
+    }
+    fn build(&mut self, world: &World) {
+        let (w, h, l) = (world.width() as f32, world.height() as f32, world.length() as f32);
+        let edge_height = self.edge.edge_height.map(|y| y as f32).unwrap_or(h / 2.);
+        let side_height = edge_height + self.edge.sides_offset as f32;
+        let side = TerrainAtlas::block_tile(self.edge.side_block);
+        let edge = TerrainAtlas::block_tile(self.edge.edge_block);
+        let mut mesh = EdgeMesh::default();
+
+        // Floor under the map, and the sides' top surface around it.
+        mesh.horizontal(vec2(0., 0.), vec2(w, l), 0., side);
+        mesh.outside(w, l, side_height, side);
+        // Walls from the floor up to the sides' surface.
+        if side_height > 0. {
+            let (x0, z0, x1, z1) = (-EPSILON, -EPSILON, w + EPSILON, l + EPSILON);
+            mesh.wall(vec3(x0, 0., z0), vec3(x1, side_height, z0), side);
+            mesh.wall(vec3(x0, 0., z1), vec3(x1, side_height, z1), side);
+            mesh.wall(vec3(x0, 0., z0), vec3(x0, side_height, z1), side);
+            mesh.wall(vec3(x1, 0., z0), vec3(x1, side_height, z1), side);
+        }
+        // Horizon, only outside the map so it never covers the world.
+        mesh.outside(w, l, edge_height, edge);
+        unsafe {
+            self.vertices.set_data(&mesh.vertices);
+            self.texcoords.set_data(&mesh.texcoords);
+        }
+    }
+}
+impl RenderStage<CubeGame> for EdgeRenderer {
+    fn run(&mut self, engine: &mut CubeGame, window: &mut GameWindow) -> anyhow::Result<()> {
+        let world = &engine.world;
+        let key = (self.edge, world.width(), world.height(), world.length());
+        if self.built != Some(key) {
+            self.build(world);
+            self.built = Some(key);
+        }
+        unsafe {
+            self.vao.bind();
+            self.shaders.bind();
+            self.shaders.set_uniform("MVP", engine.camera.matrix());
+            self.textures.texture.bind(gl::TEXTURE0);
+            window.context().disable(gl::CULL_FACE);
+            window.context().enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            let _vertices = self.vertices.bind(0);
+            let _texcoords = self.texcoords.bind(1);
+            gl::DrawArrays(gl::TRIANGLES, 0, self.vertices.len() as i32);
+            window.context().disable(gl::BLEND);
+            window.context().enable(gl::CULL_FACE);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct EdgeMesh {
+    vertices: Vec<Vec4>,
+    texcoords: Vec<Vec4>,
+}
+impl EdgeMesh {
+    /// Adds a quad from corners in block space, with
+    /// matching texture positions, tiled with `tile`.
+    fn quad(&mut self, corners: [Vec3; 4], surface: [Vec2; 4], tile: Vec2) {
+        for i in [0, 1, 2, 2, 3, 0] {
+            self.vertices.push(block_to_render(corners[i]).extend(1.));
+            self.texcoords.push(surface[i].extend(tile.x).extend(tile.y));
+        }
+    }
+    fn horizontal(&mut self, min: Vec2, max: Vec2, y: f32, tile: Vec2) {
+        self.quad(
+            [vec3(min.x, y, min.y), vec3(max.x, y, min.y), vec3(max.x, y, max.y), vec3(min.x, y, max.y)],
+            [min, vec2(max.x, min.y), max, vec2(min.x, max.y)],
+            tile,
+        );
+    }
+    /// Adds a vertical quad spanning two opposite corners.
+    fn wall(&mut self, min: Vec3, max: Vec3, tile: Vec2) {
+        let along = |p: Vec3| if min.x == max.x { p.z } else { p.x };
+        self.quad(
+            [min, vec3(max.x, min.y, max.z), max, vec3(min.x, max.y, min.z)],
+            [vec2(along(min), -min.y), vec2(along(max), -min.y), vec2(along(max), -max.y), vec2(along(min), -max.y)],
+            tile,
+        );
+    }
+    /// Covers everything within `EXTENT` of a `width` by
+    /// `length` map, except the map itself, at height `y`.
+    fn outside(&mut self, width: f32, length: f32, y: f32, tile: Vec2) {
+        let (min, max) = (-EXTENT, EXTENT);
+        self.horizontal(vec2(min, min), vec2(width + max, 0.), y, tile);
+        self.horizontal(vec2(min, length), vec2(width + max, length + max), y, tile);
+        self.horizontal(vec2(min, 0.), vec2(0., length), y, tile);
+        self.horizontal(vec2(width, 0.), vec2(width + max, length), y, tile);
+    }
+}
+
+impl EdgeRenderer {
+    const VERTEX_SHADER: &'static str = r#"
+    #version 440
+
+    layout(location = 0) in vec4 vertexPosition_modelspace;
+    layout(location = 1) in vec4 texcoords;
+    uniform mat4 MVP;
+    out vec4 fTexCoords;
+    void main() {
+        fTexCoords = texcoords;
+        gl_Position = MVP * vertexPosition_modelspace;
+    }
+    "#;
+    const FRAGMENT_SHADER: &'static str = r#"
+    #version 420
+
+in vec4 fTexCoords;
+out vec4 color;
+uniform sampler2D terrainTexture;
+
+void main() {
+    // Repeat a single atlas tile once per block.
+    vec2 uv = (fTexCoords.zw + fract(fTexCoords.xy)) / 16.0;
+    color = texture(terrainTexture, uv);
+}"#;
+}
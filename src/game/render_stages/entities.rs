@@ -0,0 +1,211 @@
+use std::{f32::consts::FRAC_PI_2, rc::Rc};
+
+use fnv::{FnvHashMap, FnvHashSet};
+use glam::{vec2, Mat4, Vec2, Vec3, Vec4};
+use image::{DynamicImage, GenericImageView, RgbaImage, Rgba};
+
+use crate::{render::{stage::RenderStage, window::GameWindow, opengl::{vao::VertexArrayObject, buffer::VertexBuffer, program::ShaderProgram, texture::Texture2D}}, game::{CubeGame, texture::TerrainAtlas, world::{block_to_render, BLOCK_SIZE}, entity::{Entity, model::{EntityModel, ModelTexture, PartKind}}}};
+
+/// Folder of cached skins, each named after its player.
+const SKIN_DIRECTORY: &str = "skins";
+/// Skin used by players without one in the cache.
+const DEFAULT_SKIN: &str = "char.png";
+/// Furthest limbs swing while walking, in radians.
+const MAX_SWING: f32 = 0.8;
+
+struct LoadedTexture {
+    texture: Texture2D,
+    /// In pixels.
+    size: Vec2,
+}
+
+struct PartMesh {
+    kind: PartKind,
+    pivot: Vec3,
+    vertices: VertexBuffer<Vec4>,
+    texcoords: VertexBuffer<Vec2>,
+}
+
+/// Draws other players and entities with their models and skins.
+pub struct EntityRenderer {
+    vao: VertexArrayObject,
+    shaders: ShaderProgram,
+    /// Uploaded models, by model name.
+    meshes: FnvHashMap<String, Vec<PartMesh>>,
+    /// Loaded skins and model textures, by path.
+    textures: FnvHashMap<String, LoadedTexture>,
+    /// Paths which failed to load, so they aren't retried every frame.
+    missing: FnvHashSet<String>,
+    terrain: Rc<TerrainAtlas>,
+}
+impl EntityRenderer {
+    pub fn init(_w: &mut GameWindow, terrain: Rc<TerrainAtlas>) -> Self {
+        unsafe {
+            let vao = VertexArrayObject::new();
+            let mut renderer = Self {
+                vao,
+                shaders: ShaderProgram::new(&[
+                    (gl::VERTEX_SHADER, Self::VERTEX_SHADER),
+                    (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
+                ]),
+                meshes: FnvHashMap::default(),
+                textures: FnvHashMap::default(),
+                missing: FnvHashSet::default(),
+                terrain,
+            };
+            let default_skin = image::open(DEFAULT_SKIN).unwrap_or_else(|e| {
+                log::warn!("Failed to load {}: {}, using a blank skin", DEFAULT_SKIN, e);
+                DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 32, Rgba([160, 160, 160, 255])))
+            });
+            renderer.textures.insert(DEFAULT_SKIN.to_string(), upload_texture(default_skin));
+            renderer
+        }
+    }
+    /// Loads `path` the first time it is asked for. Returns the key of
+    /// the texture to draw with: `path`, or the default skin if it failed.
+    fn load_texture(&mut self, path: String) -> String {
+        if !self.textures.contains_key(&path) && !self.missing.contains(&path) {
+            match image::open(&path) {
+                Ok(image) => {
+                    let texture = unsafe { upload_texture(image) };
+                    self.textures.insert(path.clone(), texture);
+                }
+                Err(e) => {
+                    log::info!("Failed to load {}: {}", path, e);
+                    self.missing.insert(path.clone());
+                }
+            }
+        }
+        if self.textures.contains_key(&path) { path } else { DEFAULT_SKIN.to_string() }
+    }
+    fn upload_model(&mut self, name: &str, model: &EntityModel) {
+        if self.meshes.contains_key(name) {
+            return;
+        }
+        let parts = model.parts.iter().map(|part| unsafe {
+            let mut vertices = VertexBuffer::new(gl::STATIC_DRAW);
+            let mut texcoords = VertexBuffer::new(gl::STATIC_DRAW);
+            vertices.set_data(&part.vertices);
+            texcoords.set_data(&part.texcoords);
+            PartMesh { kind: part.kind, pivot: part.pivot, vertices, texcoords }
+        }).collect();
+        self.meshes.insert(name.to_string(), parts);
+    }
+}
+
+/// # Safety
+/// Needs a current GL context.
+unsafe fn upload_texture(image: DynamicImage) -> LoadedTexture {
+    let size = vec2(image.width() as f32, image.height() as f32);
+    // Skins may be saved without an alpha channel.
+    let texture = Texture2D::from_image(DynamicImage::ImageRgba8(image.to_rgba8()));
+    LoadedTexture { texture, size }
+}
+
+/// Where a player's skin would be in the cache. Colour
+/// codes and anything unsafe in a file name are dropped.
+fn skin_path(name: &str) -> String {
+    let name = name.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '_').collect::<String>();
+    format!("{}/{}.png", SKIN_DIRECTORY, name)
+}
+
+/// The rotation of a part for the current pose.
+fn part_rotation(kind: PartKind, entity: &Entity, model: &EntityModel) -> Mat4 {
+    let swing = entity.walk_phase.sin() * entity.swing * MAX_SWING;
+    let arms = if model.arms_forward { FRAC_PI_2 } else { 0. };
+    match kind {
+        // Positive pitch looks down, which is negative about X.
+        PartKind::Head => Mat4::from_rotation_x(-entity.pitch.to_radians()),
+        PartKind::Body => Mat4::IDENTITY,
+        PartKind::RightArm => Mat4::from_rotation_x(arms + swing),
+        PartKind::LeftArm => Mat4::from_rotation_x(arms - swing),
+        PartKind::RightLeg => Mat4::from_rotation_x(-swing),
+        PartKind::LeftLeg => Mat4::from_rotation_x(swing),
+    }
+}
+
+impl RenderStage<CubeGame> for EntityRenderer {
+    fn run(&mut self, engine: &mut CubeGame, window: &mut GameWindow) -> anyhow::Result<()> {
+        if engine.entities.is_empty() {
+            return Ok(());
+        }
+        let camera = engine.camera.matrix();
+        unsafe {
+            self.vao.bind();
+            self.shaders.bind();
+            window.context().disable(gl::CULL_FACE);
+        }
+        for (id, entity) in engine.entities.iter() {
+            let appearance = engine.appearances.get(id).cloned().unwrap_or_default();
+            let name = appearance.model.to_lowercase();
+            let model = engine.models.get(&name);
+            self.upload_model(&name, model);
+            let (texture, tex_scale) = match model.texture {
+                ModelTexture::Terrain => (None, Vec2::ONE),
+                ModelTexture::Skin | ModelTexture::File(_) => {
+                    let path = match model.texture {
+                        ModelTexture::File(file) => file.to_string(),
+                        _ => skin_path(&engine.chat.colors.strip(&entity.name)),
+                    };
+                    let key = self.load_texture(path);
+                    // Texture coordinates are pixels of an image `texture_size`
+                    // wide. Taller skins, like 64x64 ones, share the top half.
+                    let size = self.textures[&key].size;
+                    let width = model.texture_size.x;
+                    (Some(key), Vec2::ONE / vec2(width, width * size.y / size.x))
+                }
+            };
+            let root = Mat4::from_translation(block_to_render(entity.position))
+                * Mat4::from_scale(Vec3::splat(BLOCK_SIZE))
+                * Mat4::from_rotation_y(-entity.yaw.to_radians())
+                * appearance.transform(model);
+            unsafe {
+                match &texture {
+                    Some(key) => self.textures[key].texture.bind(gl::TEXTURE0),
+                    None => self.terrain.texture.bind(gl::TEXTURE0),
+                }
+                self.shaders.set_uniform("texScale", tex_scale);
+                for part in self.meshes.get_mut(&name).unwrap() {
+                    let pose = Mat4::from_translation(part.pivot)
+                        * part_rotation(part.kind, entity, model)
+                        * Mat4::from_translation(-part.pivot);
+                    self.shaders.set_uniform("MVP", camera * root * pose);
+                    let _vertices = part.vertices.bind(0);
+                    let _texcoords = part.texcoords.bind(1);
+                    gl::DrawArrays(gl::TRIANGLES, 0, part.vertices.len() as i32);
+                }
+            }
+        }
+        window.context().enable(gl::CULL_FACE);
+        Ok(())
+    }
+}
+
+impl EntityRenderer {
+    const VERTEX_SHADER: &'static str = r#"
+    #version 440
+
+    layout(location = 0) in vec4 vertexPosition_modelspace;
+    layout(location = 1) in vec2 texcoords;
+    uniform mat4 MVP;
+    uniform vec2 texScale;
+    out vec2 fTexCoords;
+    void main() {
+        fTexCoords = texcoords * texScale;
+        gl_Position = MVP * vertexPosition_modelspace;
+    }
+    "#;
+    const FRAGMENT_SHADER: &'static str = r#"
+    #version 420
+
+in vec2 fTexCoords;
+out vec4 color;
+uniform sampler2D skin;
+
+void main() {
+    color = texture(skin, fTexCoords);
+    if (color.a < 0.5) {
+        discard;
+    }
+}"#;
+}
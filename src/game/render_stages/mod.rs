@@ -1,21 +1,59 @@
 use std::{cell::RefCell, rc::Rc};
 
-use self::{world::WorldRenderer, sky::SkyRenderer, text::TextRenderer};
+use self::{world::WorldRenderer, sky::SkyRenderer, text::TextRenderer, selection::SelectionRenderer, particles::ParticleRenderer, weather::WeatherRenderer, edge::EdgeRenderer, entities::EntityRenderer};
 
 use super::{engine::GameEngine, CubeGame};
+
+/// GLSL for facing quads towards the camera, for `concat!`ing
+/// into vertex shaders ahead of `main`.
+macro_rules! billboard_glsl {
+    () => {
+        r#"
+    // Offsets across the screen by `offset` world units. The camera's
+    // right and up axes are the first two rows of the view matrix.
+    vec3 billboard(mat4 view, vec2 offset) {
+        vec3 right = vec3(view[0][0], view[1][0], view[2][0]);
+        vec3 up = vec3(view[0][1], view[1][1], view[2][1]);
+        return right * offset.x + up * offset.y;
+    }
+"#
+    };
+}
+
 mod world;
 mod sky;
-mod text;
+pub mod text;
+pub mod selection;
+pub mod particles;
+pub mod weather;
+pub mod edge;
+mod entities;
 pub struct RenderManager {
     pub sky: Rc<RefCell<SkyRenderer>>,
     pub world: Rc<RefCell<WorldRenderer>>,
+    pub edge: Rc<RefCell<EdgeRenderer>>,
+    pub selection: Rc<RefCell<SelectionRenderer>>,
+    pub particles: Rc<RefCell<ParticleRenderer>>,
+    pub weather: Rc<RefCell<WeatherRenderer>>,
     pub text: Rc<RefCell<TextRenderer>>,
 }
 impl RenderManager {
     pub fn new(engine: &mut GameEngine<CubeGame>) -> Self {
+        let sky = engine.add_render_stage(SkyRenderer::init);
+        let world = engine.add_render_stage(WorldRenderer::init);
+        let terrain = world.borrow().terrain();
+        let edge_terrain = terrain.clone();
+        let edge = engine.add_render_stage(move |w| EdgeRenderer::init(w, edge_terrain.clone()));
+        // Entities are drawn from the game state, so nothing keeps a handle.
+        let entity_terrain = terrain.clone();
+        engine.add_render_stage(move |w| EntityRenderer::init(w, entity_terrain.clone()));
         Self {
-            sky: engine.add_render_stage(SkyRenderer::init),
-            world: engine.add_render_stage(WorldRenderer::init),
+            sky,
+            world,
+            edge,
+            selection: engine.add_render_stage(SelectionRenderer::init),
+            particles: engine.add_render_stage(move |w| ParticleRenderer::init(w, terrain.clone())),
+            weather: engine.add_render_stage(WeatherRenderer::init),
             text: engine.add_render_stage(TextRenderer::init),
         }
     }
@@ -0,0 +1,274 @@
+use std::{rc::Rc, time::{SystemTime, UNIX_EPOCH}};
+
+use fnv::FnvHashMap;
+use glam::{vec2, vec3, Vec2, Vec3, Vec4};
+
+use crate::{render::{stage::RenderStage, window::GameWindow, opengl::{vao::VertexArrayObject, buffer::VertexBuffer, program::ShaderProgram}}, game::{CubeGame, texture::TerrainAtlas, world::{World, BlockPosition, BlockCollision, block_collision, block_to_render, BLOCK_SIZE}}};
+
+/// Most particles alive at once; spawns past this are dropped.
+const MAX_PARTICLES: usize = 4096;
+
+/// Stops at liquids.
+const COLLIDES_LIQUID: u8 = 0x01;
+/// Stops at solid blocks.
+const COLLIDES_SOLID: u8 = 0x04;
+/// Dies instead of stopping.
+const EXPIRES_ON_COLLISION: u8 = 0x80;
+
+/// A particle effect defined by the server through DefineEffect.
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleEffect {
+    /// Texture coordinates of the first frame in the terrain
+    /// atlas as `(u1, v1, u2, v2)`, 0-1. Further frames
+    /// follow to the right.
+    pub region: Vec4,
+    /// RGB, 0-1.
+    pub tint: Vec3,
+    pub frame_count: u8,
+    /// Particles made by each SpawnEffect.
+    pub particle_count: u8,
+    /// Edge length, in blocks.
+    pub size: f32,
+    /// Fraction by which the size may randomly shrink.
+    pub size_variation: f32,
+    /// Largest distance from the spawn point, in blocks.
+    pub spread: f32,
+    /// In blocks per second.
+    pub speed: f32,
+    /// In blocks per second squared.
+    pub gravity: f32,
+    /// In seconds.
+    pub lifetime: f32,
+    /// Fraction by which the lifetime may randomly shrink.
+    pub lifetime_variation: f32,
+    pub collide_flags: u8,
+}
+
+struct Particle {
+    effect: ParticleEffect,
+    /// In blocks.
+    position: Vec3,
+    /// In blocks per second.
+    velocity: Vec3,
+    size: f32,
+    age: f32,
+    lifetime: f32,
+}
+impl Particle {
+    /// Advances the particle by `delta` seconds,
+    /// returning false once it should be removed.
+    fn update(&mut self, world: &World, delta: f32) -> bool {
+        self.age += delta;
+        if self.age >= self.lifetime {
+            return false;
+        }
+        self.velocity.y -= self.effect.gravity * delta;
+        // Move one axis at a time so a particle
+        // hitting a wall can still slide along it.
+        for axis in 0..3 {
+            let mut next = self.position;
+            next[axis] += self.velocity[axis] * delta;
+            if self.collides(world, next) {
+                if self.effect.collide_flags & EXPIRES_ON_COLLISION != 0 {
+                    return false;
+                }
+                self.velocity[axis] = 0.;
+            } else {
+                self.position = next;
+            }
+        }
+        true
+    }
+    fn collides(&self, world: &World, position: Vec3) -> bool {
+        let flags = self.effect.collide_flags;
+        match block_collision(block_at(world, position)) {
+            BlockCollision::Gas => false,
+            BlockCollision::Liquid => flags & COLLIDES_LIQUID != 0,
+            BlockCollision::Solid => flags & COLLIDES_SOLID != 0,
+        }
+    }
+    /// The texture region of the current animation frame.
+    fn frame(&self) -> Vec4 {
+        let frames = self.effect.frame_count.max(1) as f32;
+        let frame = (self.age / self.lifetime * frames).floor().min(frames - 1.);
+        let region = self.effect.region;
+        let offset = (region.z - region.x) * frame;
+        region + Vec4::new(offset, 0., offset, 0.)
+    }
+}
+
+fn block_at(world: &World, position: Vec3) -> u16 {
+    let p = position.floor();
+    world.get_block(BlockPosition::new(p.x as i32, p.y as i32, p.z as i32))
+}
+
+/// Xorshift generator. Particles only need cheap noise.
+struct Random(u32);
+impl Random {
+    fn new() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        Self(seed | 1)
+    }
+    /// Returns a number in `0..1`.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+    /// Returns a number in `-1..1`.
+    fn signed(&mut self) -> f32 {
+        self.next() * 2. - 1.
+    }
+}
+
+/// Draws CustomParticles effects as camera-facing
+/// quads, one instance per particle.
+pub struct ParticleRenderer {
+    effects: FnvHashMap<u8, ParticleEffect>,
+    particles: Vec<Particle>,
+    random: Random,
+    vao: VertexArrayObject,
+    corners: VertexBuffer<Vec2>,
+    /// Per instance: render space center and size.
+    centers: VertexBuffer<Vec4>,
+    /// Per instance: atlas region of the current frame.
+    regions: VertexBuffer<Vec4>,
+    /// Per instance: RGBA tint.
+    tints: VertexBuffer<Vec4>,
+    shaders: ShaderProgram,
+    textures: Rc<TerrainAtlas>,
+}
+impl ParticleRenderer {
+    /// Defines an effect, replacing any with the same ID.
+    pub fn define_effect(&mut self, id: u8, effect: ParticleEffect) {
+        self.effects.insert(id, effect);
+    }
+    /// Spawns a defined effect at `position`. Particles fly
+    /// away from `origin`; both are in blocks.
+    pub fn spawn_effect(&mut self, id: u8, position: Vec3, origin: Vec3) {
+        let effect = match self.effects.get(&id) {
+            Some(effect) => *effect,
+            None => {
+                log::warn!("Server spawned undefined particle effect {}", id);
+                return;
+            }
+        };
+        for _ in 0..effect.particle_count {
+            if self.particles.len() >= MAX_PARTICLES {
+                break;
+            }
+            let random = &mut self.random;
+            let offset = vec3(random.signed(), random.signed(), random.signed()) * effect.spread;
+            let position = position + offset;
+            let velocity = (position - origin).normalize_or_zero() * effect.speed;
+            let size = effect.size * (1. - effect.size_variation * random.next());
+            let lifetime = effect.lifetime * (1. - effect.lifetime_variation * random.next());
+            self.particles.push(Particle {
+                effect,
+                position,
+                velocity,
+                size,
+                age: 0.,
+                lifetime,
+            });
+        }
+    }
+    pub fn init(_w: &mut GameWindow, textures: Rc<TerrainAtlas>) -> Self {
+        unsafe {
+            let vao = VertexArrayObject::new();
+            let mut corners = VertexBuffer::new(gl::STATIC_DRAW);
+            corners.set_data(&[
+                vec2(0., 0.), vec2(1., 0.), vec2(1., 1.),
+                vec2(1., 1.), vec2(0., 1.), vec2(0., 0.),
+            ]);
+            Self {
+                effects: FnvHashMap::default(),
+                particles: Vec::new(),
+                random: Random::new(),
+                vao,
+                corners,
+                centers: VertexBuffer::new(gl::STREAM_DRAW),
+                regions: VertexBuffer::new(gl::STREAM_DRAW),
+                tints: VertexBuffer::new(gl::STREAM_DRAW),
+                shaders: ShaderProgram::new(&[
+                    (gl::VERTEX_SHADER, Self::VERTEX_SHADER),
+                    (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
+                ]),
+                textures,
+            }
+        }
+    }
+}
+impl RenderStage<CubeGame> for ParticleRenderer {
+    fn run(&mut self, engine: &mut CubeGame, window: &mut GameWindow) -> anyhow::Result<()> {
+        let delta = window.delta_time().as_secs_f32();
+        let world = &engine.world;
+        self.particles.retain_mut(|p| p.update(world, delta));
+        if self.particles.is_empty() {
+            return Ok(());
+        }
+        let mut centers = Vec::with_capacity(self.particles.len());
+        let mut regions = Vec::with_capacity(self.particles.len());
+        let mut tints = Vec::with_capacity(self.particles.len());
+        for particle in &self.particles {
+            centers.push(block_to_render(particle.position).extend(particle.size * BLOCK_SIZE));
+            regions.push(particle.frame());
+            tints.push(particle.effect.tint.extend(1.));
+        }
+        unsafe {
+            self.centers.set_data(&centers);
+            self.regions.set_data(&regions);
+            self.tints.set_data(&tints);
+            self.vao.bind();
+            self.shaders.bind();
+            self.shaders.set_uniform("view", engine.camera.view());
+            self.shaders.set_uniform("projection", engine.camera.projection());
+            self.textures.texture.bind(gl::TEXTURE0);
+            window.context().disable(gl::CULL_FACE);
+            let _corners = self.corners.bind(0);
+            let _centers = self.centers.bind_instanced(1);
+            let _regions = self.regions.bind_instanced(2);
+            let _tints = self.tints.bind_instanced(3);
+            gl::DrawArraysInstanced(gl::TRIANGLES, 0, self.corners.len() as i32, self.particles.len() as i32);
+            window.context().enable(gl::CULL_FACE);
+        }
+        Ok(())
+    }
+}
+
+impl ParticleRenderer {
+    const VERTEX_SHADER: &'static str = concat!(r#"
+    #version 440
+
+    layout(location = 0) in vec2 corner;
+    layout(location = 1) in vec4 centerSize;
+    layout(location = 2) in vec4 region;
+    layout(location = 3) in vec4 tint;
+    uniform mat4 view;
+    uniform mat4 projection;
+    out vec2 fTexCoords;
+    out vec4 fTint;"#, billboard_glsl!(), r#"
+    void main() {
+        vec3 offset = billboard(view, corner - 0.5) * centerSize.w;
+        fTexCoords = mix(region.xy, region.zw, vec2(corner.x, 1.0 - corner.y));
+        fTint = tint;
+        gl_Position = projection * view * vec4(centerSize.xyz + offset, 1.0);
+    }
+    "#);
+    const FRAGMENT_SHADER: &'static str = r#"
+    #version 420
+
+in vec2 fTexCoords;
+in vec4 fTint;
+out vec4 color;
+uniform sampler2D terrainTexture;
+
+void main() {
+    vec4 texel = texture(terrainTexture, fTexCoords);
+    if (texel.a < 0.5) {
+        discard;
+    }
+    color = texel * fTint;
+}"#;
+}
@@ -0,0 +1,166 @@
+use fnv::FnvHashMap;
+use glam::{vec4, Mat4, Vec3, Vec4};
+
+use crate::{render::{stage::RenderStage, window::GameWindow, opengl::{vao::VertexArrayObject, buffer::VertexBuffer, program::ShaderProgram}}, game::{CubeGame, world::block_to_render}};
+
+/// Pushed out from the selected blocks to
+/// avoid z-fighting with their faces.
+const EPSILON: f32 = 1. / 32.;
+
+/// A server-defined cuboid highlighting a region of the world.
+pub struct Selection {
+    /// Lowest corner, in block space.
+    pub min: Vec3,
+    /// Highest corner, in block space.
+    pub max: Vec3,
+    /// RGBA, 0-1.
+    pub color: Vec4,
+}
+impl Selection {
+    /// Creates a selection spanning two corners in
+    /// any order. Colour components range from 0-255.
+    pub fn new(start: Vec3, end: Vec3, color: Vec4) -> Self {
+        Self {
+            min: start.min(end),
+            max: start.max(end),
+            color: color / 255.,
+        }
+    }
+    fn model(&self) -> Mat4 {
+        let min = block_to_render(self.min - Vec3::splat(EPSILON));
+        let max = block_to_render(self.max + Vec3::splat(EPSILON));
+        Mat4::from_translation(min) * Mat4::from_scale(max - min)
+    }
+}
+
+pub struct SelectionRenderer {
+    selections: FnvHashMap<u8, Selection>,
+    vao: VertexArrayObject,
+    faces: VertexBuffer<Vec4>,
+    edges: VertexBuffer<Vec4>,
+    shaders: ShaderProgram,
+}
+impl SelectionRenderer {
+    /// Adds a selection, replacing any with the same ID.
+    pub fn add_selection(&mut self, id: u8, selection: Selection) {
+        self.selections.insert(id, selection);
+    }
+    pub fn remove_selection(&mut self, id: u8) {
+        self.selections.remove(&id);
+    }
+    pub fn init(_w: &mut GameWindow) -> Self {
+        unsafe {
+            let vao = VertexArrayObject::new();
+            let mut faces = VertexBuffer::new(gl::STATIC_DRAW);
+            let mut edges = VertexBuffer::new(gl::STATIC_DRAW);
+            faces.set_data(&unit_cube_faces());
+            edges.set_data(&unit_cube_edges());
+            Self {
+                selections: FnvHashMap::default(),
+                vao,
+                faces,
+                edges,
+                shaders: ShaderProgram::new(&[
+                    (gl::VERTEX_SHADER, Self::VERTEX_SHADER),
+                    (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
+                ]),
+            }
+        }
+    }
+}
+impl RenderStage<CubeGame> for SelectionRenderer {
+    fn run(&mut self, engine: &mut CubeGame, window: &mut GameWindow) -> anyhow::Result<()> {
+        if self.selections.is_empty() {
+            return Ok(());
+        }
+        // Translucent boxes must be drawn back to front.
+        let camera = engine.camera.position;
+        let mut selections = self.selections.values().collect::<Vec<&Selection>>();
+        selections.sort_by(|a, b| {
+            let da = block_to_render((a.min + a.max) / 2.).distance_squared(camera);
+            let db = block_to_render((b.min + b.max) / 2.).distance_squared(camera);
+            db.total_cmp(&da)
+        });
+        unsafe {
+            self.vao.bind();
+            self.shaders.bind();
+            window.context().disable(gl::CULL_FACE);
+            window.context().enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            window.context().depth_mask(false);
+            let matrix = engine.camera.matrix();
+            for selection in selections {
+                self.shaders.set_uniform("MVP", matrix * selection.model());
+
+                self.shaders.set_uniform("color", selection.color);
+                let _binding = self.faces.bind(0);
+                gl::DrawArrays(gl::TRIANGLES, 0, self.faces.len() as i32);
+                drop(_binding);
+
+                // Outlines are drawn opaque in the same colour.
+                let c = selection.color;
+                self.shaders.set_uniform("color", vec4(c.x, c.y, c.z, 1.));
+                let _binding = self.edges.bind(0);
+                gl::DrawArrays(gl::LINES, 0, self.edges.len() as i32);
+            }
+            window.context().depth_mask(true);
+            window.context().disable(gl::BLEND);
+            window.context().enable(gl::CULL_FACE);
+        }
+        Ok(())
+    }
+}
+
+fn unit_cube_faces() -> Vec<Vec4> {
+    const FACES: [[[f32; 3]; 4]; 6] = [
+        [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+        [[0., 0., 1.], [1., 0., 1.], [1., 1., 1.], [0., 1., 1.]],
+        [[0., 0., 0.], [1., 0., 0.], [1., 0., 1.], [0., 0., 1.]],
+        [[0., 1., 0.], [1., 1., 0.], [1., 1., 1.], [0., 1., 1.]],
+        [[0., 0., 0.], [0., 1., 0.], [0., 1., 1.], [0., 0., 1.]],
+        [[1., 0., 0.], [1., 1., 0.], [1., 1., 1.], [1., 0., 1.]],
+    ];
+    let mut vertices = Vec::with_capacity(36);
+    for [a, b, c, d] in FACES {
+        for v in [a, b, c, c, d, a] {
+            vertices.push(Vec3::from(v).extend(1.));
+        }
+    }
+    vertices
+}
+
+fn unit_cube_edges() -> Vec<Vec4> {
+    let mut vertices = Vec::with_capacity(24);
+    for a in [0., 1.] {
+        for b in [0., 1.] {
+            vertices.push(vec4(0., a, b, 1.));
+            vertices.push(vec4(1., a, b, 1.));
+            vertices.push(vec4(a, 0., b, 1.));
+            vertices.push(vec4(a, 1., b, 1.));
+            vertices.push(vec4(a, b, 0., 1.));
+            vertices.push(vec4(a, b, 1., 1.));
+        }
+    }
+    vertices
+}
+
+impl SelectionRenderer {
+    const VERTEX_SHADER: &'static str = r#"
+    #version 440
+
+    layout(location = 0) in vec4 vertexPosition_modelspace;
+    uniform mat4 MVP;
+    void main() {
+        gl_Position = MVP * vertexPosition_modelspace;
+    }
+    "#;
+    const FRAGMENT_SHADER: &'static str = r#"
+    #version 420
+
+out vec4 fragColor;
+uniform vec4 color;
+
+void main() {
+    fragColor = color;
+}"#;
+}
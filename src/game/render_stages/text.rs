@@ -6,6 +6,9 @@ use glam::{vec4, Vec4, Mat4, Mat3, Vec2, IVec2, ivec2, Vec3, vec2, vec3};
 
 use crate::{render::{stage::RenderStage, window::GameWindow, opengl::{vao::VertexArrayObject, buffer::VertexBuffer, program::ShaderProgram, texture::Texture2D}}, game::CubeGame};
 
+/// Height glyphs are rasterized at, in pixels.
+pub const FONT_SIZE: u32 = 48;
+
 struct Character {
     texture: Texture2D,
     size: IVec2,
@@ -18,24 +21,53 @@ impl Character {
     }
 }
 
-struct TextRenderRequest {
+/// A run of text drawn in one colour.
+#[derive(Clone, Debug)]
+pub struct TextSegment {
     pub text: String,
+    pub color: Vec3,
+}
+impl TextSegment {
+    pub fn new(text: impl Into<String>, color: Vec3) -> Self {
+        Self { text: text.into(), color }
+    }
+}
+
+struct TextRenderRequest {
+    pub segments: Vec<TextSegment>,
     pub position: Vec2,
     pub scale: f32,
-    pub color: Vec3,
+}
+
+/// Text placed in the world, facing the camera.
+struct WorldTextRequest {
+    pub segments: Vec<TextSegment>,
+    /// Bottom center of the text, in render space.
+    pub anchor: Vec3,
+    /// Render space units per glyph pixel.
+    pub scale: f32,
 }
 
 pub struct TextRenderer {
     requests: Vec<TextRenderRequest>,
+    world_requests: Vec<WorldTextRequest>,
     vao: VertexArrayObject,
     shaders: ShaderProgram,
+    world_shaders: ShaderProgram,
     chars: FnvHashMap<char, Character>,
     vertices: VertexBuffer<Vec2>,
     texcoords: VertexBuffer<Vec2>,
 }
 impl TextRenderer {
-    pub fn render(&mut self, string: String, position: Vec2, scale: f32, color: Vec3) {
-        self.requests.push(TextRenderRequest { text: string, position, scale, color });
+    /// Queues text for this frame. Segments are drawn one
+    /// after another, each in its own colour.
+    pub fn render(&mut self, segments: Vec<TextSegment>, position: Vec2, scale: f32) {
+        self.requests.push(TextRenderRequest { segments, position, scale });
+    }
+    /// Queues text for this frame, centered above `anchor` in render
+    /// space and turned towards the camera. It is hidden behind blocks.
+    pub fn render_world(&mut self, segments: Vec<TextSegment>, anchor: Vec3, scale: f32) {
+        self.world_requests.push(WorldTextRequest { segments, anchor, scale });
     }
     pub fn init(w: &mut GameWindow) -> Self {
         unsafe {
@@ -45,9 +77,13 @@ impl TextRenderer {
                 (gl::VERTEX_SHADER, Self::VERTEX_SHADER),
                 (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
             ]);
+            let world_shaders = ShaderProgram::new(&[
+                (gl::VERTEX_SHADER, Self::WORLD_VERTEX_SHADER),
+                (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
+            ]);
             let lib = Library::init().unwrap();
             let face = lib.new_face("minecraft_font.ttf", 0).unwrap();
-            face.set_pixel_sizes(0, 48).unwrap();
+            face.set_pixel_sizes(0, FONT_SIZE).unwrap();
             let mut f = LoadFlag::empty();
             f.set(LoadFlag::RENDER, true);
             let mut characters = FnvHashMap::default();
@@ -78,6 +114,8 @@ impl TextRenderer {
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
             Self {
                 requests: Vec::new(),
+                world_requests: Vec::new(),
+                world_shaders,
                 vertices,
                 texcoords,
                 chars: characters,
@@ -91,14 +129,35 @@ impl RenderStage<CubeGame> for TextRenderer {
     fn run(&mut self, engine: &mut CubeGame, window: &mut crate::render::window::GameWindow) -> anyhow::Result<()> {
         unsafe {
             self.vao.bind();
-            self.shaders.bind();
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            // World text is depth tested against the
+            // world, but doesn't hide what's behind it.
+            window.context().disable(gl::CULL_FACE);
+            window.context().depth_mask(false);
+            self.world_shaders.bind();
+            self.world_shaders.set_uniform("view", engine.camera.view());
+            self.world_shaders.set_uniform("projection", engine.camera.projection());
+            for request in self.world_requests.drain(..).collect::<Vec<WorldTextRequest>>() {
+                self.world_shaders.set_uniform("anchor", request.anchor);
+                let mut x = -self.text_width(&request.segments, request.scale) / 2.;
+                for segment in request.segments {
+                    x = self.render_text(true, segment.text, x, 0., request.scale, segment.color);
+                }
+            }
+            window.context().depth_mask(true);
+            window.context().enable(gl::CULL_FACE);
+
             gl::Disable(gl::DEPTH_TEST);
+            self.shaders.bind();
             let projection = Mat4::orthographic_lh(0.0, window.size().width as f32, 0.0, window.size().height as f32, 0.5, 1.5);
             self.shaders.set_uniform("projection", projection);
             for request in self.requests.drain(..).collect::<Vec<TextRenderRequest>>() {
-                self.render_text(engine, request.text, request.position.x, request.position.y, request.scale, request.color);
+                let mut x = request.position.x;
+                for segment in request.segments {
+                    x = self.render_text(false, segment.text, x, request.position.y, request.scale, segment.color);
+                }
             }
             gl::Enable(gl::DEPTH_TEST);
             gl::Disable(gl::BLEND);
@@ -107,9 +166,20 @@ impl RenderStage<CubeGame> for TextRenderer {
     }
 }
 impl TextRenderer {
-    unsafe fn render_text(&mut self, engine: &mut CubeGame, text: String, mut x: f32, mut y: f32, scale: f32, color: Vec3) {
-        self.shaders.bind();
-        self.shaders.set_uniform("textColor", color);
+    /// Width of the segments laid out in a row.
+    fn text_width(&self, segments: &[TextSegment], scale: f32) -> f32 {
+        segments.iter()
+            .flat_map(|s| s.text.chars())
+            .filter_map(|c| self.chars.get(&c))
+            .map(|ch| (ch.advance >> 6) as f32 * scale)
+            .sum()
+    }
+    /// Draws `text` starting at `x`, returning where it ended. `world`
+    /// picks the world space shaders over the screen space ones.
+    unsafe fn render_text(&mut self, world: bool, text: String, mut x: f32, y: f32, scale: f32, color: Vec3) -> f32 {
+        let shaders = if world { &self.world_shaders } else { &self.shaders };
+        shaders.bind();
+        shaders.set_uniform("textColor", color);
         for c in text.chars() {
             if let Some(ch) = self.chars.get(&c) {
                 let xpos = x + ch.bearing.x as f32 * scale;
@@ -150,6 +220,7 @@ impl TextRenderer {
                 x += (ch.advance >> 6) as f32 * scale;
             }
         }
+        x
     }
 }
 impl TextRenderer {
@@ -168,6 +239,23 @@ impl TextRenderer {
         TexCoords = texcoords;
     }
     "#;
+    const WORLD_VERTEX_SHADER: &'static str = concat!(r#"
+    #version 440
+
+    layout(location = 0) in vec2 vertex;
+    layout(location = 1) in vec2 texcoords;
+
+    out vec2 TexCoords;
+
+    uniform mat4 view;
+    uniform mat4 projection;
+    uniform vec3 anchor;
+"#, billboard_glsl!(), r#"
+    void main() {
+        gl_Position = projection * view * vec4(anchor + billboard(view, vertex), 1.0);
+        TexCoords = texcoords;
+    }
+    "#);
     const FRAGMENT_SHADER: &'static str = r#"
     #version 420
     out vec4 color;
@@ -0,0 +1,216 @@
+use glam::{vec3, vec4, Vec3, Vec4};
+
+use crate::{render::{stage::RenderStage, window::GameWindow, opengl::{vao::VertexArrayObject, buffer::VertexBuffer, program::ShaderProgram}}, game::{CubeGame, world::{World, block_to_render}}};
+
+/// Columns this far from the camera get
+/// precipitation, in blocks.
+const RADIUS: i32 = 10;
+/// How far above and below the camera
+/// precipitation is drawn, in blocks.
+const HEIGHT: f32 = 12.;
+
+/// Weather set by EnvSetWeatherType.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WeatherType {
+    Sunny,
+    Raining,
+    Snowing,
+}
+impl WeatherType {
+    pub fn from_byte(weather: u8) -> Option<Self> {
+        match weather {
+            0 => Some(WeatherType::Sunny),
+            1 => Some(WeatherType::Raining),
+            2 => Some(WeatherType::Snowing),
+            _ => None,
+        }
+    }
+}
+
+/// Draws rain or snow falling around the camera. Each column
+/// stops at its highest opaque block, so covered areas stay dry.
+pub struct WeatherRenderer {
+    weather: WeatherType,
+    /// Highest blocking Y per column of the map, in Z, X order,
+    /// filled in as columns come into range and cleared when
+    /// blocks change. `None` for columns not yet looked up.
+    heights: Vec<Option<Option<i32>>>,
+    /// Width and length of the map `heights` was made for.
+    heights_size: (usize, usize),
+    /// Seconds the current weather has been falling.
+    time: f32,
+    vao: VertexArrayObject,
+    vertices: VertexBuffer<Vec4>,
+    /// Per vertex: horizontal and vertical
+    /// position on the sheet, and a column seed.
+    texcoords: VertexBuffer<Vec4>,
+    shaders: ShaderProgram,
+}
+impl WeatherRenderer {
+    pub fn set_weather(&mut self, weather: WeatherType) {
+        self.weather = weather;
+    }
+    /// Forgets the cached height of a column after a block in it changed.
+    pub fn invalidate_column(&mut self, x: i32, z: i32) {
+        if let Some(index) = self.column_index(x, z) {
+            self.heights[index] = None;
+        }
+    }
+    fn column_index(&self, x: i32, z: i32) -> Option<usize> {
+        let (width, length) = self.heights_size;
+        if x < 0 || z < 0 || x as usize >= width || z as usize >= length {
+            return None;
+        }
+        Some(z as usize * width + x as usize)
+    }
+    fn column_height(&mut self, world: &World, x: i32, z: i32) -> Option<i32> {
+        let size = (world.width(), world.length());
+        if self.heights_size != size {
+            self.heights = vec![None; size.0 * size.1];
+            self.heights_size = size;
+        }
+        let index = self.column_index(x, z)?;
+        *self.heights[index].get_or_insert_with(|| world.column_height(x, z))
+    }
+    pub fn init(_w: &mut GameWindow) -> Self {
+        unsafe {
+            let vao = VertexArrayObject::new();
+            Self {
+                weather: WeatherType::Sunny,
+                heights: Vec::new(),
+                heights_size: (0, 0),
+                time: 0.,
+                vao,
+                vertices: VertexBuffer::new(gl::STREAM_DRAW),
+                texcoords: VertexBuffer::new(gl::STREAM_DRAW),
+                shaders: ShaderProgram::new(&[
+                    (gl::VERTEX_SHADER, Self::VERTEX_SHADER),
+                    (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
+                ]),
+            }
+        }
+    }
+}
+impl RenderStage<CubeGame> for WeatherRenderer {
+    fn run(&mut self, engine: &mut CubeGame, window: &mut GameWindow) -> anyhow::Result<()> {
+        if self.weather == WeatherType::Sunny {
+            self.time = 0.;
+            return Ok(());
+        }
+        self.time += window.delta_time().as_secs_f32();
+        let eye = engine.player.eye_position();
+        let (cx, cz) = (eye.x.floor() as i32, eye.z.floor() as i32);
+        let mut vertices = Vec::new();
+        let mut texcoords = Vec::new();
+        for x in cx - RADIUS..=cx + RADIUS {
+            for z in cz - RADIUS..=cz + RADIUS {
+                let (dx, dz) = (x - cx, z - cz);
+                if dx * dx + dz * dz > RADIUS * RADIUS {
+                    continue;
+                }
+                let top = eye.y + HEIGHT;
+                let bottom = match self.column_height(&engine.world, x, z) {
+                    Some(y) => (y + 1) as f32,
+                    None => eye.y - HEIGHT,
+                }.max(eye.y - HEIGHT);
+                if bottom >= top {
+                    continue;
+                }
+                let seed = ((x * 31 + z * 17).rem_euclid(64)) as f32 / 64.;
+                // Two sheets crossing at the column's center.
+                let center = vec3(x as f32 + 0.5, 0., z as f32 + 0.5);
+                for axis in [Vec3::X, Vec3::Z] {
+                    let a = center - axis * 0.5;
+                    let b = center + axis * 0.5;
+                    let corners = [(a, bottom, 0.), (b, bottom, 1.), (b, top, 1.), (b, top, 1.), (a, top, 0.), (a, bottom, 0.)];
+                    for (p, y, u) in corners {
+                        vertices.push(block_to_render(vec3(p.x, y, p.z)).extend(1.));
+                        texcoords.push(vec4(u, y, seed, 0.));
+                    }
+                }
+            }
+        }
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            self.vertices.set_data(&vertices);
+            self.texcoords.set_data(&texcoords);
+            self.vao.bind();
+            self.shaders.bind();
+            self.shaders.set_uniform("MVP", engine.camera.matrix());
+            self.shaders.set_uniform("time", self.time);
+            self.shaders.set_uniform("snowing", (self.weather == WeatherType::Snowing) as i32);
+            self.shaders.set_uniform("eye", block_to_render(eye));
+            window.context().disable(gl::CULL_FACE);
+            window.context().enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            window.context().depth_mask(false);
+            let _vertices = self.vertices.bind(0);
+            let _texcoords = self.texcoords.bind(1);
+            gl::DrawArrays(gl::TRIANGLES, 0, self.vertices.len() as i32);
+            window.context().depth_mask(true);
+            window.context().disable(gl::BLEND);
+            window.context().enable(gl::CULL_FACE);
+        }
+        Ok(())
+    }
+}
+
+impl WeatherRenderer {
+    const VERTEX_SHADER: &'static str = r#"
+    #version 440
+
+    layout(location = 0) in vec4 vertexPosition_modelspace;
+    layout(location = 1) in vec4 sheet;
+    uniform mat4 MVP;
+    out vec4 fSheet;
+    out vec3 fPos;
+    void main() {
+        fSheet = sheet;
+        fPos = vertexPosition_modelspace.xyz;
+        gl_Position = MVP * vertexPosition_modelspace;
+    }
+    "#;
+    const FRAGMENT_SHADER: &'static str = r#"
+    #version 420
+
+in vec4 fSheet;
+in vec3 fPos;
+out vec4 color;
+uniform float time;
+uniform int snowing;
+uniform vec3 eye;
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    float u = fSheet.x;
+    float seed = fSheet.z;
+    float alpha;
+    if (snowing == 1) {
+        // Flakes drift slowly on a 4x4 grid per block.
+        vec2 p = vec2(u * 4.0, fSheet.y * 4.0 + time * 4.0 + seed * 16.0);
+        vec2 cell = floor(p);
+        vec2 offset = vec2(hash(cell), hash(cell + 7.0)) * 0.6 + 0.2;
+        float flake = 1.0 - smoothstep(0.1, 0.2, distance(fract(p), offset));
+        alpha = flake * step(0.5, hash(cell + 3.0));
+        color.rgb = vec3(1.0);
+    } else {
+        // Thin streaks falling quickly.
+        float lane = floor(u * 8.0);
+        float v = fSheet.y + time * 16.0 + hash(vec2(lane, seed)) * 8.0;
+        float streak = step(0.85, fract(u * 8.0)) * step(0.7, fract(v * 0.25));
+        alpha = streak * step(0.4, hash(vec2(lane, seed + 1.0))) * 0.6;
+        color.rgb = vec3(0.55, 0.65, 0.9);
+    }
+    // Fade out towards the edge of the drawn area.
+    alpha *= 1.0 - smoothstep(2.0, 5.0, distance(fPos.xz, eye.xz));
+    if (alpha <= 0.01) {
+        discard;
+    }
+    color.a = alpha;
+}"#;
+}
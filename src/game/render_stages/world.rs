@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::{rc::Rc, time::Instant};
 
 use glam::{vec4, Vec4, Mat4, Vec2};
 
@@ -9,7 +9,7 @@ pub struct WorldRenderer {
     vbo: VertexBuffer<Vec4>,
     texcoords: VertexBuffer<Vec2>,
     shaders: ShaderProgram,
-    textures: TerrainAtlas,
+    textures: Rc<TerrainAtlas>,
     mesh: MeshBuilder,
     last_poll: Instant,
 }
@@ -21,6 +21,10 @@ impl WorldRenderer {
         let end = Instant::now();
         //log::info!("Took {}ms", (end - start).as_millis());
     }
+    /// The terrain atlas, shared with the other stages drawing blocks.
+    pub fn terrain(&self) -> Rc<TerrainAtlas> {
+        self.textures.clone()
+    }
     pub fn poll(&mut self) {
         if self.mesh.poll() {
             self.mesh.set_data(&mut self.vbo, &mut self.texcoords);
@@ -56,7 +60,7 @@ impl WorldRenderer {
                     (gl::VERTEX_SHADER, Self::VERTEX_SHADER),
                     (gl::FRAGMENT_SHADER, Self::FRAGMENT_SHADER),
                 ]),
-                textures: TerrainAtlas::load_from_file("terrain.png").unwrap()
+                textures: Rc::new(TerrainAtlas::load_from_file("terrain.png").unwrap())
             }
         }
     }
@@ -64,6 +68,9 @@ impl WorldRenderer {
 impl RenderStage<CubeGame> for WorldRenderer {
     fn run(&mut self, engine: &mut CubeGame, window: &mut crate::render::window::GameWindow) -> anyhow::Result<()> {
         unsafe {
+            for chunk in engine.world.take_dirty_chunks() {
+                self.build_chunk(engine.world.clone(), chunk);
+            }
             let now = Instant::now();
             if now.duration_since(self.last_poll).as_millis() > 25 {
                 self.poll();
@@ -4,12 +4,38 @@ use glam::{Vec2, vec2};
 
 use crate::render::opengl::texture::Texture2D;
 
-use super::world::Facing;
+use super::world::{Facing, BlockId};
 
 pub struct TerrainAtlas {
     pub texture: Texture2D,
 }
 impl TerrainAtlas {
+    /// Returns the atlas tile, in tiles, shown on top of a block.
+    pub fn block_tile(block: BlockId) -> Vec2 {
+        let (x, y) = match block {
+            1 => (1, 0),
+            2 => (0, 0),
+            3 => (2, 0),
+            4 => (0, 1),
+            5 => (4, 0),
+            7 => (1, 1),
+            8 | 9 => (14, 0),
+            10 | 11 => (14, 1),
+            12 => (2, 1),
+            13 => (3, 1),
+            17 => (5, 1),
+            18 => (6, 1),
+            19 => (0, 3),
+            20 => (1, 3),
+            21..=36 => (block as i32 - 21, 4),
+            41 => (8, 1),
+            42 => (7, 1),
+            45 => (7, 0),
+            49 => (5, 2),
+            _ => (0, 0),
+        };
+        vec2(x as f32, y as f32)
+    }
     pub fn load_from_file(terrain_png: impl Into<PathBuf>) -> anyhow::Result<Self> {
         unsafe {
             let texture = Texture2D::from_image(image::open(terrain_png.into())?);
@@ -0,0 +1,458 @@
+//! The terrain generator from Classic 0.30, as reproduced by
+//! ClassiCube. The same seed and size always give the same map.
+
+use std::f32::consts::PI;
+
+use super::{noise::{CombinedNoise, JavaRandom, OctaveNoise}, WorldGenerator};
+
+const AIR: u8 = 0;
+const STONE: u8 = 1;
+const GRASS: u8 = 2;
+const DIRT: u8 = 3;
+const WATER: u8 = 8;
+const LAVA: u8 = 10;
+const SAND: u8 = 12;
+const GRAVEL: u8 = 13;
+const GOLD_ORE: u8 = 14;
+const IRON_ORE: u8 = 15;
+const COAL_ORE: u8 = 16;
+const LOG: u8 = 17;
+const LEAVES: u8 = 18;
+const DANDELION: u8 = 37;
+const BROWN_MUSHROOM: u8 = 39;
+
+pub struct ClassicWorldGenerator {
+    seed: i64,
+}
+
+impl ClassicWorldGenerator {
+    pub fn new(seed: i64) -> Self {
+        Self { seed }
+    }
+}
+
+impl WorldGenerator for ClassicWorldGenerator {
+    fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize) {
+        if width == 0 || height == 0 || length == 0 {
+            return;
+        }
+        let mut generator = Generator {
+            blocks: data,
+            width: width as i32,
+            height: height as i32,
+            length: length as i32,
+            water_level: height as i32 / 2,
+            heightmap: Vec::with_capacity(width * length),
+            random: JavaRandom::new(self.seed),
+        };
+        generator.blocks.fill(AIR);
+        generator.heightmap();
+        generator.strata();
+        generator.caves();
+        generator.ore_veins(COAL_ORE, 0.9);
+        generator.ore_veins(IRON_ORE, 0.7);
+        generator.ore_veins(GOLD_ORE, 0.5);
+        generator.flood_water_borders();
+        generator.flood_water_sources();
+        generator.flood_lava_sources();
+        generator.surface();
+        generator.flowers();
+        generator.mushrooms();
+        generator.trees();
+    }
+}
+
+/// State shared by the generation steps, which must run in
+/// order since they all draw from the same random numbers.
+struct Generator<'a> {
+    blocks: &'a mut [u8],
+    width: i32,
+    height: i32,
+    length: i32,
+    water_level: i32,
+    /// Height of the terrain in each column, in Z, X order.
+    heightmap: Vec<i32>,
+    random: JavaRandom,
+}
+
+impl Generator<'_> {
+    fn volume(&self) -> i32 {
+        self.width * self.height * self.length
+    }
+    fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        x >= 0 && y >= 0 && z >= 0 && x < self.width && y < self.height && z < self.length
+    }
+    fn index(&self, x: i32, y: i32, z: i32) -> usize {
+        ((y * self.length + z) * self.width + x) as usize
+    }
+    fn get(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.blocks[self.index(x, y, z)]
+    }
+    fn set(&mut self, x: i32, y: i32, z: i32, block: u8) {
+        let index = self.index(x, y, z);
+        self.blocks[index] = block;
+    }
+    fn column_height(&self, x: i32, z: i32) -> i32 {
+        self.heightmap[(z * self.width + x) as usize]
+    }
+    /// Moves a coordinate up to five blocks either way, as plant patches spread.
+    fn scatter(&mut self, value: i32) -> i32 {
+        value + self.random.next_int(6) - self.random.next_int(6)
+    }
+
+    fn heightmap(&mut self) {
+        let low = CombinedNoise::new(&mut self.random, 8, 8);
+        let high = CombinedNoise::new(&mut self.random, 8, 8);
+        let select = OctaveNoise::new(&mut self.random, 6);
+        for z in 0..self.length {
+            for x in 0..self.width {
+                let (fx, fz) = (x as f32, z as f32);
+                let low_height = low.sample(fx * 1.3, fz * 1.3) / 6. - 4.;
+                let mut height = low_height;
+                if select.sample(fx, fz) <= 0. {
+                    let high_height = high.sample(fx * 1.3, fz * 1.3) / 5. + 6.;
+                    height = height.max(high_height);
+                }
+                height *= 0.5;
+                if height < 0. {
+                    height *= 0.8;
+                }
+                self.heightmap.push((height + self.water_level as f32) as i32);
+            }
+        }
+    }
+
+    /// Lava at the bottom, then stone, then a layer of dirt up to the heightmap.
+    fn strata(&mut self) {
+        let noise = OctaveNoise::new(&mut self.random, 8);
+        let max_y = self.height - 1;
+        for z in 0..self.length {
+            for x in 0..self.width {
+                let dirt_thickness = (noise.sample(x as f32, z as f32) / 24. - 4.) as i32;
+                let dirt_height = self.column_height(x, z);
+                let stone_height = (dirt_height + dirt_thickness).min(max_y);
+                let dirt_height = dirt_height.min(max_y);
+                self.set(x, 0, z, LAVA);
+                for y in 1..=stone_height {
+                    self.set(x, y, z, STONE);
+                }
+                for y in (stone_height + 1).max(1)..=dirt_height {
+                    self.set(x, y, z, DIRT);
+                }
+            }
+        }
+    }
+
+    fn caves(&mut self) {
+        let (width, height, length) = (self.width, self.height, self.length);
+        for _ in 0..self.volume() / 8192 {
+            let mut cave_x = self.random.next_int(width) as f32;
+            let mut cave_y = self.random.next_int(height) as f32;
+            let mut cave_z = self.random.next_int(length) as f32;
+            let cave_length = (self.random.next_float() * self.random.next_float() * 200.) as i32;
+            let mut theta = self.random.next_float() * PI * 2.;
+            let mut delta_theta = 0.;
+            let mut phi = self.random.next_float() * PI * 2.;
+            let mut delta_phi = 0.;
+            let cave_radius = self.random.next_float() * self.random.next_float();
+
+            for i in 0..cave_length {
+                cave_x += theta.sin() * phi.cos();
+                cave_z += theta.cos() * phi.cos();
+                cave_y += phi.sin();
+                theta += delta_theta * 0.2;
+                delta_theta = delta_theta * 0.9 + self.random.next_float() - self.random.next_float();
+                phi = phi * 0.5 + delta_phi * 0.25;
+                delta_phi = delta_phi * 0.75 + self.random.next_float() - self.random.next_float();
+                if self.random.next_float() < 0.25 {
+                    continue;
+                }
+                let center_x = (cave_x + (self.random.next_int(4) - 2) as f32 * 0.2) as i32;
+                let center_y = (cave_y + (self.random.next_int(4) - 2) as f32 * 0.2) as i32;
+                let center_z = (cave_z + (self.random.next_int(4) - 2) as f32 * 0.2) as i32;
+                let radius = (height - center_y) as f32 / height as f32;
+                let radius = 1.2 + (radius * 3.5 + 1.) * cave_radius;
+                let radius = radius * (i as f32 * PI / cave_length as f32).sin();
+                self.fill_oblate_spheroid(center_x, center_y, center_z, radius, AIR);
+            }
+        }
+    }
+
+    fn ore_veins(&mut self, ore: u8, abundance: f32) {
+        let (width, height, length) = (self.width, self.height, self.length);
+        for _ in 0..(self.volume() as f32 * abundance / 16384.) as i32 {
+            let mut vein_x = self.random.next_int(width) as f32;
+            let mut vein_y = self.random.next_int(height) as f32;
+            let mut vein_z = self.random.next_int(length) as f32;
+            let vein_length = self.random.next_float() * self.random.next_float() * 75. * abundance;
+            let mut theta = self.random.next_float() * PI * 2.;
+            let mut delta_theta = 0.;
+            let mut phi = self.random.next_float() * PI * 2.;
+            let mut delta_phi = 0.;
+
+            let mut i = 0.;
+            while i < vein_length {
+                vein_x += theta.sin() * phi.cos();
+                vein_z += theta.cos() * phi.cos();
+                vein_y += phi.sin();
+                // Classic sets rather than adds here, so veins barely turn.
+                theta = delta_theta * 0.2;
+                delta_theta = delta_theta * 0.9 + self.random.next_float() - self.random.next_float();
+                phi = phi * 0.5 + delta_phi * 0.25;
+                delta_phi = delta_phi * 0.9 + self.random.next_float() - self.random.next_float();
+                let radius = abundance * (i * PI / vein_length).sin() + 1.;
+                self.fill_oblate_spheroid(vein_x as i32, vein_y as i32, vein_z as i32, radius, ore);
+                i += 1.;
+            }
+        }
+    }
+
+    /// Replaces stone within a sphere squashed to half its height.
+    fn fill_oblate_spheroid(&mut self, x: i32, y: i32, z: i32, radius: f32, block: u8) {
+        let start = |center: i32| (center as f32 - radius).max(0.) as i32;
+        let end = |center: i32, size: i32| (center as f32 + radius).min((size - 1) as f32).floor() as i32;
+        let radius_squared = radius * radius;
+        for yy in start(y)..=end(y, self.height) {
+            for zz in start(z)..=end(z, self.length) {
+                for xx in start(x)..=end(x, self.width) {
+                    let (dx, dy, dz) = ((xx - x) as f32, (yy - y) as f32, (zz - z) as f32);
+                    if dx * dx + 2. * dy * dy + dz * dz < radius_squared && self.get(xx, yy, zz) == STONE {
+                        self.set(xx, yy, zz, block);
+                    }
+                }
+            }
+        }
+    }
+
+    fn flood_water_borders(&mut self) {
+        let y = self.water_level - 1;
+        if y < 0 {
+            return;
+        }
+        for x in 0..self.width {
+            self.flood_fill(x, y, 0, WATER);
+            self.flood_fill(x, y, self.length - 1, WATER);
+        }
+        for z in 0..self.length {
+            self.flood_fill(0, y, z, WATER);
+            self.flood_fill(self.width - 1, y, z, WATER);
+        }
+    }
+
+    fn flood_water_sources(&mut self) {
+        for _ in 0..self.width * self.length / 800 {
+            let x = self.random.next_int(self.width);
+            let z = self.random.next_int(self.length);
+            let y = self.water_level - self.random.range(1, 3);
+            self.flood_fill(x, y, z, WATER);
+        }
+    }
+
+    fn flood_lava_sources(&mut self) {
+        for _ in 0..self.width * self.length / 20000 {
+            let x = self.random.next_int(self.width);
+            let z = self.random.next_int(self.length);
+            let y = ((self.water_level - 3) as f32 * self.random.next_float() * self.random.next_float()) as i32;
+            self.flood_fill(x, y, z, LAVA);
+        }
+    }
+
+    /// Fills the air connected to a block sideways and downwards.
+    fn flood_fill(&mut self, x: i32, y: i32, z: i32, block: u8) {
+        let mut stack = vec![(x, y, z)];
+        while let Some((x, y, z)) = stack.pop() {
+            if !self.contains(x, y, z) || self.get(x, y, z) != AIR {
+                continue;
+            }
+            self.set(x, y, z, block);
+            stack.extend_from_slice(&[(x - 1, y, z), (x + 1, y, z), (x, y, z - 1), (x, y, z + 1), (x, y - 1, z)]);
+        }
+    }
+
+    /// Grass on land, with sand on beaches and gravel under the sea.
+    fn surface(&mut self) {
+        let sand = OctaveNoise::new(&mut self.random, 8);
+        let gravel = OctaveNoise::new(&mut self.random, 8);
+        for z in 0..self.length {
+            for x in 0..self.width {
+                let y = self.column_height(x, z);
+                if y < 0 || y >= self.height {
+                    continue;
+                }
+                let above = if y + 1 < self.height { self.get(x, y + 1, z) } else { AIR };
+                let (fx, fz) = (x as f32, z as f32);
+                if above == WATER && gravel.sample(fx, fz) > 12. {
+                    self.set(x, y, z, GRAVEL);
+                } else if above == AIR {
+                    let block = if y <= self.water_level && sand.sample(fx, fz) > 8. { SAND } else { GRASS };
+                    self.set(x, y, z, block);
+                }
+            }
+        }
+    }
+
+    fn flowers(&mut self) {
+        for _ in 0..self.width * self.length / 3000 {
+            let flower = DANDELION + self.random.next_int(2) as u8;
+            let patch_x = self.random.next_int(self.width);
+            let patch_z = self.random.next_int(self.length);
+            for _ in 0..10 {
+                let (mut x, mut z) = (patch_x, patch_z);
+                for _ in 0..5 {
+                    x = self.scatter(x);
+                    z = self.scatter(z);
+                    if !self.contains(x, 0, z) {
+                        continue;
+                    }
+                    let y = self.column_height(x, z) + 1;
+                    if y <= 0 || y >= self.height {
+                        continue;
+                    }
+                    if self.get(x, y, z) == AIR && self.get(x, y - 1, z) == GRASS {
+                        self.set(x, y, z, flower);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Mushrooms on cave floors, below the surface.
+    fn mushrooms(&mut self) {
+        for _ in 0..self.volume() / 2000 {
+            let mushroom = BROWN_MUSHROOM + self.random.next_int(2) as u8;
+            let patch_x = self.random.next_int(self.width);
+            let y = self.random.next_int(self.height);
+            let patch_z = self.random.next_int(self.length);
+            for _ in 0..20 {
+                let (mut x, mut z) = (patch_x, patch_z);
+                for _ in 0..5 {
+                    x = self.scatter(x);
+                    z = self.scatter(z);
+                    if !self.contains(x, 0, z) || y >= self.column_height(x, z) - 1 || y == 0 {
+                        continue;
+                    }
+                    if self.get(x, y, z) == AIR && self.get(x, y - 1, z) == STONE {
+                        self.set(x, y, z, mushroom);
+                    }
+                }
+            }
+        }
+    }
+
+    fn trees(&mut self) {
+        for _ in 0..self.width * self.length / 4000 {
+            let patch_x = self.random.next_int(self.width);
+            let patch_z = self.random.next_int(self.length);
+            for _ in 0..20 {
+                let (mut x, mut z) = (patch_x, patch_z);
+                for _ in 0..20 {
+                    x = self.scatter(x);
+                    z = self.scatter(z);
+                    if !self.contains(x, 0, z) || self.random.next_float() >= 0.25 {
+                        continue;
+                    }
+                    let y = self.column_height(x, z) + 1;
+                    if y >= self.height {
+                        continue;
+                    }
+                    let tree_height = 5 + self.random.next_int(3);
+                    let below = if y > 0 { self.get(x, y - 1, z) } else { AIR };
+                    if below == GRASS && self.can_grow_tree(x, y, z, tree_height) {
+                        self.grow_tree(x, y, z, tree_height);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether there is room for the trunk and the leaves.
+    fn can_grow_tree(&self, x: i32, y: i32, z: i32, tree_height: i32) -> bool {
+        let trunk_height = tree_height - 4;
+        let is_clear = |layers: std::ops::Range<i32>, reach: i32| {
+            layers.into_iter().all(|yy| {
+                (z - reach..=z + reach).all(|zz| {
+                    (x - reach..=x + reach).all(|xx| self.contains(xx, yy, zz) && self.get(xx, yy, zz) == AIR)
+                })
+            })
+        };
+        is_clear(y..y + trunk_height, 1) && is_clear(y + trunk_height..y + tree_height, 2)
+    }
+
+    fn grow_tree(&mut self, x: i32, y: i32, z: i32, tree_height: i32) {
+        let top_start = y + tree_height - 2;
+        let top = y + tree_height;
+        let bottom_start = y + tree_height - 4;
+
+        // Two wide layers of leaves, with corners left out at random.
+        for yy in bottom_start..top_start {
+            for dz in -2..=2 {
+                for dx in -2..=2 {
+                    if dx * dz == 4 || dx * dz == -4 {
+                        if self.random.next_float() >= 0.5 {
+                            self.set(x + dx, yy, z + dz, LEAVES);
+                        }
+                    } else {
+                        self.set(x + dx, yy, z + dz, LEAVES);
+                    }
+                }
+            }
+        }
+        // Two narrow layers on top, only the lower of which has corners.
+        for yy in top_start..top {
+            for dz in -1..=1 {
+                for dx in -1..=1 {
+                    if dx == 0 || dz == 0 || (yy == top_start && self.random.next_float() >= 0.5) {
+                        self.set(x + dx, yy, z + dz, LEAVES);
+                    }
+                }
+            }
+        }
+        for yy in y..top - 1 {
+            self.set(x, yy, z, LOG);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::{BlockPosition, World};
+
+    fn world(seed: i64) -> World {
+        World::new(ClassicWorldGenerator::new(seed), 128, 64, 128)
+    }
+
+    fn count(world: &World, block: u8) -> usize {
+        (0..128 * 64 * 128)
+            .filter(|&i| world.index_to_position(i).and_then(|p| world.get(p)) == Some(block as u16))
+            .count()
+    }
+
+    #[test]
+    fn same_seed_gives_same_map() {
+        let (a, b, c) = (world(1), world(1), world(2));
+        let blocks = |world: &World| world.data.read().unwrap().lower.clone();
+        assert_eq!(blocks(&a), blocks(&b));
+        assert_ne!(blocks(&a), blocks(&c));
+    }
+
+    #[test]
+    fn looks_like_classic_terrain() {
+        let world = world(1234);
+        for (x, z) in [(0, 0), (64, 64), (127, 127)] {
+            assert_eq!(world.get(BlockPosition::new(x, 0, z)), Some(LAVA as u16));
+        }
+        assert!(count(&world, GRASS) > 128 * 128 / 4);
+        for block in [STONE, DIRT, WATER, COAL_ORE, LOG, LEAVES] {
+            assert!(count(&world, block) > 0, "no {}", block);
+        }
+        // Only the flood fill places water, which never leaves air beneath it.
+        for i in 0..128 * 64 * 128 {
+            let position = world.index_to_position(i).unwrap();
+            if world.get(position) == Some(WATER as u16) {
+                let below = BlockPosition::new(position.x, position.y - 1, position.z);
+                assert_ne!(world.get(below), Some(AIR as u16));
+            }
+        }
+    }
+}
@@ -0,0 +1,524 @@
+//! Reading and writing ClassicWorld (.cw) maps, the NBT
+//! format used by ClassiCube and most modern servers.
+
+use std::{
+    collections::hash_map::RandomState,
+    fs::File,
+    hash::{BuildHasher, Hasher},
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nbt::{decode::TagDecodeError, encode::write_gzip_compound_tag, CompoundTag, CompoundTagError, Tag};
+use thiserror::Error;
+
+use super::{BlockArray, BlockId, BlockPosition, World};
+
+const FORMAT_VERSION: i8 = 1;
+
+/// Error when loading or saving a ClassicWorld map.
+#[derive(Debug, Error)]
+pub enum ClassicWorldError {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid NBT: {0}")]
+    Nbt(#[from] TagDecodeError),
+    #[error("missing tag `{0}`")]
+    MissingTag(String),
+    #[error("tag `{0}` has the wrong type")]
+    WrongTagType(String),
+    #[error("unsupported format version {0}")]
+    UnsupportedVersion(i8),
+    #[error("invalid world: {0}")]
+    Invalid(String),
+}
+impl From<CompoundTagError<'_>> for ClassicWorldError {
+    fn from(e: CompoundTagError) -> Self {
+        match e {
+            CompoundTagError::TagNotFound { name } => ClassicWorldError::MissingTag(name.to_string()),
+            CompoundTagError::TagWrongType { name, .. } => ClassicWorldError::WrongTagType(name.to_string()),
+        }
+    }
+}
+
+/// Where players appear in a map.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WorldSpawn {
+    pub position: BlockPosition,
+    /// Classic packed angles.
+    pub yaw: u8,
+    pub pitch: u8,
+}
+impl Default for WorldSpawn {
+    fn default() -> Self {
+        Self { position: BlockPosition::new(0, 0, 0), yaw: 0, pitch: 0 }
+    }
+}
+
+/// The service and account a map was made with.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CreatedBy {
+    pub service: String,
+    pub username: String,
+}
+
+/// The program and generator a map was made with.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MapGenerator {
+    pub software: String,
+    pub name: String,
+}
+
+/// Colours from EnvColors. `None` keeps the client's default.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct EnvColors {
+    pub sky: Option<[u8; 3]>,
+    pub cloud: Option<[u8; 3]>,
+    pub fog: Option<[u8; 3]>,
+    pub ambient: Option<[u8; 3]>,
+    pub sunlight: Option<[u8; 3]>,
+}
+
+/// Settings from EnvMapAppearance.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MapAppearance {
+    pub texture_url: String,
+    pub side_block: u8,
+    pub edge_block: u8,
+    /// Height of the horizon, or -1 for the default.
+    pub side_level: i16,
+}
+
+/// A custom block from BlockDefinitions.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockDefinition {
+    pub id: BlockId,
+    pub name: String,
+    pub collide_type: u8,
+    pub speed: f32,
+    /// Top, bottom, left, right, front and back.
+    pub textures: [u16; 6],
+    pub transmits_light: bool,
+    pub walk_sound: u8,
+    pub full_bright: bool,
+    /// 0 for a sprite, otherwise the height in sixteenths.
+    pub shape: u8,
+    pub draw: u8,
+    /// Density, then red, green and blue.
+    pub fog: [u8; 4],
+    /// Minimum then maximum corner, in sixteenths.
+    pub coords: [u8; 6],
+}
+
+/// A map with everything a ClassicWorld file holds
+/// which the client understands.
+#[derive(Clone)]
+pub struct ClassicWorld {
+    pub world: World,
+    pub name: Option<String>,
+    pub uuid: [u8; 16],
+    pub spawn: WorldSpawn,
+    pub created_by: Option<CreatedBy>,
+    pub map_generator: Option<MapGenerator>,
+    pub env_colors: Option<EnvColors>,
+    pub map_appearance: Option<MapAppearance>,
+    pub block_definitions: Vec<BlockDefinition>,
+}
+impl ClassicWorld {
+    /// Wraps a world with a fresh UUID and no metadata.
+    pub fn new(world: World) -> Self {
+        Self {
+            world,
+            name: None,
+            uuid: new_uuid(),
+            spawn: WorldSpawn::default(),
+            created_by: None,
+            map_generator: None,
+            env_colors: None,
+            map_appearance: None,
+            block_definitions: Vec::new(),
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ClassicWorldError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+    /// Reads a map from its decompressed root tag. Use
+    /// `import::load_map` to load a map from a file.
+    pub fn from_tag(root: &CompoundTag) -> Result<Self, ClassicWorldError> {
+        let version = root.get_i8("FormatVersion")?;
+        if version != FORMAT_VERSION {
+            return Err(ClassicWorldError::UnsupportedVersion(version));
+        }
+        let (width, height, length) = (root.get_i16("X")?, root.get_i16("Y")?, root.get_i16("Z")?);
+        let lower = to_bytes(root.get_i8_vec("BlockArray")?);
+        let upper = root.get_i8_vec("BlockArray2").ok().map(|u| to_bytes(u));
+        let world = World::from_data(lower, upper, width, height, length)
+            .map_err(|e| ClassicWorldError::Invalid(e.to_string()))?;
+
+        let uuid = match root.get_i8_vec("UUID") {
+            Ok(uuid) => to_bytes(uuid).try_into().map_err(|_| ClassicWorldError::Invalid("UUID isn't 16 bytes".to_string()))?,
+            Err(_) => [0; 16],
+        };
+        let spawn = match root.get_compound_tag("Spawn") {
+            Ok(spawn) => WorldSpawn {
+                position: BlockPosition::new(
+                    spawn.get_i16("X")? as i32,
+                    spawn.get_i16("Y")? as i32,
+                    spawn.get_i16("Z")? as i32,
+                ),
+                yaw: spawn.get_i8("H").unwrap_or(0) as u8,
+                pitch: spawn.get_i8("P").unwrap_or(0) as u8,
+            },
+            Err(_) => WorldSpawn::default(),
+        };
+        let created_by = root.get_compound_tag("CreatedBy").ok().map(|tag| CreatedBy {
+            service: tag.get_str("Service").unwrap_or_default().to_string(),
+            username: tag.get_str("Username").unwrap_or_default().to_string(),
+        });
+        let map_generator = root.get_compound_tag("MapGenerator").ok().map(|tag| MapGenerator {
+            software: tag.get_str("Software").unwrap_or_default().to_string(),
+            name: tag.get_str("MapGeneratorName").unwrap_or_default().to_string(),
+        });
+
+        let cpe = root.get_compound_tag("Metadata").and_then(|m| m.get_compound_tag("CPE")).ok();
+        let env_colors = cpe.and_then(|c| c.get_compound_tag("EnvColors").ok()).map(|tag| EnvColors {
+            sky: read_color(tag, "Sky"),
+            cloud: read_color(tag, "Cloud"),
+            fog: read_color(tag, "Fog"),
+            ambient: read_color(tag, "Ambient"),
+            sunlight: read_color(tag, "Sunlight"),
+        });
+        let map_appearance = match cpe.and_then(|c| c.get_compound_tag("EnvMapAppearance").ok()) {
+            Some(tag) => Some(MapAppearance {
+                texture_url: tag.get_str("TextureURL").unwrap_or_default().to_string(),
+                side_block: tag.get_i8("SideBlock")? as u8,
+                edge_block: tag.get_i8("EdgeBlock")? as u8,
+                side_level: tag.get_i16("SideLevel")?,
+            }),
+            None => None,
+        };
+        let block_definitions = match cpe.and_then(|c| c.get_compound_tag("BlockDefinitions").ok()) {
+            Some(tag) => tag.iter()
+                .filter_map(|(_, tag)| match tag {
+                    Tag::Compound(block) => Some(read_block_definition(block)),
+                    _ => None,
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            world,
+            name: root.get_str("Name").ok().map(String::from),
+            uuid,
+            spawn,
+            created_by,
+            map_generator,
+            env_colors,
+            map_appearance,
+            block_definitions,
+        })
+    }
+    /// Writes the map, gzipped.
+    pub fn write(&self, mut writer: impl Write) -> Result<(), ClassicWorldError> {
+        let mut root = CompoundTag::named("ClassicWorld");
+        root.insert_i8("FormatVersion", FORMAT_VERSION);
+        if let Some(name) = &self.name {
+            root.insert_str("Name", name);
+        }
+        root.insert_i8_vec("UUID", to_signed(&self.uuid));
+        root.insert_i16("X", self.world.width() as i16);
+        root.insert_i16("Y", self.world.height() as i16);
+        root.insert_i16("Z", self.world.length() as i16);
+        if let Some(created_by) = &self.created_by {
+            let mut tag = CompoundTag::new();
+            tag.insert_str("Service", &created_by.service);
+            tag.insert_str("Username", &created_by.username);
+            root.insert_compound_tag("CreatedBy", tag);
+        }
+        if let Some(generator) = &self.map_generator {
+            let mut tag = CompoundTag::new();
+            tag.insert_str("Software", &generator.software);
+            tag.insert_str("MapGeneratorName", &generator.name);
+            root.insert_compound_tag("MapGenerator", tag);
+        }
+        let mut spawn = CompoundTag::new();
+        spawn.insert_i16("X", self.spawn.position.x as i16);
+        spawn.insert_i16("Y", self.spawn.position.y as i16);
+        spawn.insert_i16("Z", self.spawn.position.z as i16);
+        spawn.insert_i8("H", self.spawn.yaw as i8);
+        spawn.insert_i8("P", self.spawn.pitch as i8);
+        root.insert_compound_tag("Spawn", spawn);
+        {
+            let data = self.world.data.read().unwrap();
+            let BlockArray { lower, upper } = &*data;
+            root.insert_i8_vec("BlockArray", to_signed(lower));
+            if let Some(upper) = upper {
+                root.insert_i8_vec("BlockArray2", to_signed(upper));
+            }
+        }
+
+        let mut cpe = CompoundTag::new();
+        if let Some(colors) = &self.env_colors {
+            let mut tag = CompoundTag::new();
+            tag.insert_i32("ExtensionVersion", 1);
+            write_color(&mut tag, "Sky", colors.sky);
+            write_color(&mut tag, "Cloud", colors.cloud);
+            write_color(&mut tag, "Fog", colors.fog);
+            write_color(&mut tag, "Ambient", colors.ambient);
+            write_color(&mut tag, "Sunlight", colors.sunlight);
+            cpe.insert_compound_tag("EnvColors", tag);
+        }
+        if let Some(appearance) = &self.map_appearance {
+            let mut tag = CompoundTag::new();
+            tag.insert_i32("ExtensionVersion", 1);
+            tag.insert_str("TextureURL", &appearance.texture_url);
+            tag.insert_i8("SideBlock", appearance.side_block as i8);
+            tag.insert_i8("EdgeBlock", appearance.edge_block as i8);
+            tag.insert_i16("SideLevel", appearance.side_level);
+            cpe.insert_compound_tag("EnvMapAppearance", tag);
+        }
+        if !self.block_definitions.is_empty() {
+            let mut tag = CompoundTag::new();
+            tag.insert_i32("ExtensionVersion", 1);
+            for definition in &self.block_definitions {
+                tag.insert_compound_tag(format!("Block{}", definition.id), write_block_definition(definition));
+            }
+            cpe.insert_compound_tag("BlockDefinitions", tag);
+        }
+        if !cpe.is_empty() {
+            let mut metadata = CompoundTag::new();
+            metadata.insert_compound_tag("CPE", cpe);
+            root.insert_compound_tag("Metadata", metadata);
+        }
+        write_gzip_compound_tag(&mut writer, &root)?;
+        Ok(())
+    }
+}
+
+fn to_bytes(data: &[i8]) -> Vec<u8> {
+    data.iter().map(|&b| b as u8).collect()
+}
+
+fn to_signed(data: &[u8]) -> Vec<i8> {
+    data.iter().map(|&b| b as i8).collect()
+}
+
+/// A random version 4 UUID.
+fn new_uuid() -> [u8; 16] {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let mut uuid = [0; 16];
+    for half in uuid.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(time);
+        half.copy_from_slice(&hasher.finish().to_be_bytes());
+    }
+    uuid[6] = (uuid[6] & 0x0F) | 0x40;
+    uuid[8] = (uuid[8] & 0x3F) | 0x80;
+    uuid
+}
+
+/// Reads an EnvColors entry, where negative components mean the default.
+fn read_color(tag: &CompoundTag, name: &str) -> Option<[u8; 3]> {
+    let color = tag.get_compound_tag(name).ok()?;
+    let (r, g, b) = (color.get_i16("R").ok()?, color.get_i16("G").ok()?, color.get_i16("B").ok()?);
+    if r < 0 || g < 0 || b < 0 {
+        return None;
+    }
+    Some([r as u8, g as u8, b as u8])
+}
+
+fn write_color(tag: &mut CompoundTag, name: &str, color: Option<[u8; 3]>) {
+    let [r, g, b] = color.map(|c| c.map(i16::from)).unwrap_or([-1; 3]);
+    let mut color = CompoundTag::new();
+    color.insert_i16("R", r);
+    color.insert_i16("G", g);
+    color.insert_i16("B", b);
+    tag.insert_compound_tag(name, color);
+}
+
+fn read_block_definition(tag: &CompoundTag) -> Result<BlockDefinition, ClassicWorldError> {
+    // Blocks past 255 keep their full ID in ID2.
+    let id = match tag.get_i16("ID2") {
+        Ok(id) => id as BlockId,
+        Err(_) => tag.get_i8("ID")? as u8 as BlockId,
+    };
+    // A second set of six bytes holds the upper bits of texture IDs.
+    let raw = to_bytes(tag.get_i8_vec("Textures")?);
+    if raw.len() < 6 {
+        return Err(ClassicWorldError::Invalid(format!("block {} has too few textures", id)));
+    }
+    let mut textures = [0; 6];
+    for (i, texture) in textures.iter_mut().enumerate() {
+        *texture = raw[i] as u16 | (raw.get(i + 6).copied().unwrap_or(0) as u16) << 8;
+    }
+    let shape = tag.get_i8("Shape")? as u8;
+    let fog = match tag.get_i8_vec("Fog") {
+        Ok(fog) => to_bytes(fog).try_into().map_err(|_| ClassicWorldError::Invalid(format!("block {} has a bad fog", id)))?,
+        Err(_) => [0; 4],
+    };
+    let coords = match tag.get_i8_vec("Coords") {
+        Ok(coords) => to_bytes(coords).try_into().map_err(|_| ClassicWorldError::Invalid(format!("block {} has bad bounds", id)))?,
+        Err(_) => [0, 0, 0, 16, shape.max(1), 16],
+    };
+    Ok(BlockDefinition {
+        id,
+        name: tag.get_str("Name")?.to_string(),
+        collide_type: tag.get_i8("CollideType")? as u8,
+        speed: tag.get_f32("Speed")?,
+        textures,
+        transmits_light: tag.get_i8("TransmitsLight")? != 0,
+        walk_sound: tag.get_i8("WalkSound")? as u8,
+        full_bright: tag.get_i8("FullBright")? != 0,
+        shape,
+        draw: tag.get_i8("BlockDraw")? as u8,
+        fog,
+        coords,
+    })
+}
+
+fn write_block_definition(definition: &BlockDefinition) -> CompoundTag {
+    let mut tag = CompoundTag::new();
+    tag.insert_i8("ID", definition.id as u8 as i8);
+    if definition.id > 255 {
+        tag.insert_i16("ID2", definition.id as i16);
+    }
+    tag.insert_str("Name", &definition.name);
+    tag.insert_i8("CollideType", definition.collide_type as i8);
+    tag.insert_f32("Speed", definition.speed);
+    let mut textures = definition.textures.iter().map(|&t| t as u8 as i8).collect::<Vec<i8>>();
+    if definition.textures.iter().any(|&t| t > 255) {
+        textures.extend(definition.textures.iter().map(|&t| (t >> 8) as u8 as i8));
+    }
+    tag.insert_i8_vec("Textures", textures);
+    tag.insert_i8("TransmitsLight", definition.transmits_light as i8);
+    tag.insert_i8("WalkSound", definition.walk_sound as i8);
+    tag.insert_i8("FullBright", definition.full_bright as i8);
+    tag.insert_i8("Shape", definition.shape as i8);
+    tag.insert_i8("BlockDraw", definition.draw as i8);
+    tag.insert_i8_vec("Fog", to_signed(&definition.fog));
+    tag.insert_i8_vec("Coords", to_signed(&definition.coords));
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use nbt::decode::read_gzip_compound_tag;
+
+    use super::*;
+    use crate::game::world::FlatWorldGenerator;
+
+    fn read(data: &[u8]) -> Result<ClassicWorld, ClassicWorldError> {
+        ClassicWorld::from_tag(&read_gzip_compound_tag(&mut &data[..])?)
+    }
+
+    fn sample() -> ClassicWorld {
+        let mut world = World::new(FlatWorldGenerator::new(2, 1, 2, 0), 8, 4, 6);
+        world.set(BlockPosition::new(7, 3, 5), 300).unwrap();
+        let mut level = ClassicWorld::new(world);
+        level.name = Some("test".to_string());
+        level.spawn = WorldSpawn { position: BlockPosition::new(4, 3, 2), yaw: 200, pitch: 10 };
+        level.created_by = Some(CreatedBy { service: "ClassiCube".to_string(), username: "someone".to_string() });
+        level.map_generator = Some(MapGenerator { software: "exoglgame".to_string(), name: "flat".to_string() });
+        level.env_colors = Some(EnvColors { sky: Some([10, 200, 255]), fog: Some([0, 0, 0]), ..Default::default() });
+        level.map_appearance = Some(MapAppearance {
+            texture_url: "http://example.com/terrain.png".to_string(),
+            side_block: 1,
+            edge_block: 9,
+            side_level: -1,
+        });
+        level.block_definitions = vec![
+            BlockDefinition {
+                id: 70,
+                name: "Half glass".to_string(),
+                collide_type: 2,
+                speed: 1.5,
+                textures: [1, 2, 3, 4, 5, 6],
+                transmits_light: true,
+                walk_sound: 3,
+                full_bright: false,
+                shape: 8,
+                draw: 1,
+                fog: [0; 4],
+                coords: [0, 0, 0, 16, 8, 16],
+            },
+            BlockDefinition {
+                id: 300,
+                name: "Glowing".to_string(),
+                collide_type: 2,
+                speed: 1.,
+                textures: [260, 2, 3, 4, 5, 511],
+                transmits_light: false,
+                walk_sound: 1,
+                full_bright: true,
+                shape: 16,
+                draw: 0,
+                fog: [20, 1, 2, 3],
+                coords: [0, 0, 0, 16, 16, 16],
+            },
+        ];
+        level
+    }
+
+    #[test]
+    fn round_trips() {
+        let level = sample();
+        let mut data = Vec::new();
+        level.write(&mut data).unwrap();
+        let read = read(&data).unwrap();
+
+        assert_eq!(read.name, level.name);
+        assert_eq!(read.uuid, level.uuid);
+        assert_eq!(read.spawn, level.spawn);
+        assert_eq!(read.created_by, level.created_by);
+        assert_eq!(read.map_generator, level.map_generator);
+        assert_eq!(read.env_colors, level.env_colors);
+        assert_eq!(read.map_appearance, level.map_appearance);
+        assert_eq!(read.block_definitions, level.block_definitions);
+        assert_eq!((read.world.width(), read.world.height(), read.world.length()), (8, 4, 6));
+        let (a, b) = (read.world.data.read().unwrap(), level.world.data.read().unwrap());
+        assert_eq!(a.lower, b.lower);
+        assert_eq!(a.upper, b.upper);
+        assert_eq!(read.world.get(BlockPosition::new(7, 3, 5)), Some(300));
+    }
+
+    #[test]
+    fn minimal_world_has_defaults() {
+        let level = ClassicWorld::new(World::new(FlatWorldGenerator::new(1, 1, 1, 0), 2, 2, 2));
+        let mut data = Vec::new();
+        level.write(&mut data).unwrap();
+        let read = read(&data).unwrap();
+        assert_eq!(read.name, None);
+        assert_eq!(read.env_colors, None);
+        assert!(read.block_definitions.is_empty());
+        assert_eq!(read.uuid[6] >> 4, 4);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(matches!(read(b"not a map"), Err(ClassicWorldError::Nbt(_))));
+
+        let mut root = CompoundTag::named("ClassicWorld");
+        root.insert_i8("FormatVersion", 2);
+        let mut data = Vec::new();
+        write_gzip_compound_tag(&mut data, &root).unwrap();
+        assert!(matches!(read(&data), Err(ClassicWorldError::UnsupportedVersion(2))));
+
+        root.insert_i8("FormatVersion", 1);
+        let mut data = Vec::new();
+        write_gzip_compound_tag(&mut data, &root).unwrap();
+        assert!(matches!(read(&data), Err(ClassicWorldError::MissingTag(name)) if name == "X"));
+
+        root.insert_i16("X", 2);
+        root.insert_i16("Y", 2);
+        root.insert_i16("Z", 2);
+        root.insert_i8_vec("BlockArray", vec![0; 7]);
+        let mut data = Vec::new();
+        write_gzip_compound_tag(&mut data, &root).unwrap();
+        assert!(matches!(read(&data), Err(ClassicWorldError::Invalid(_))));
+    }
+}
@@ -0,0 +1,373 @@
+//! Themed generators for quickly making test maps, picked
+//! by name like the MCGalaxy `/newlvl` themes.
+
+use super::{classic::ClassicWorldGenerator, noise::{JavaRandom, OctaveNoise}, FlatWorldGenerator, WorldGenerator};
+
+const AIR: u8 = 0;
+const STONE: u8 = 1;
+const GRASS: u8 = 2;
+const DIRT: u8 = 3;
+const BEDROCK: u8 = 7;
+const WATER: u8 = 8;
+const SAND: u8 = 12;
+const SANDSTONE: u8 = 52;
+
+/// Theme names accepted by [`theme`].
+pub const THEMES: [&str; 8] = ["flat", "classic", "island", "floatland", "mountains", "desert", "empty", "void"];
+
+/// Returns the generator for a theme with default parameters, or
+/// `None` for an unknown theme. `height` is the height of the map,
+/// which places the ground of flat maps halfway up.
+pub fn theme(name: &str, seed: i64, height: usize) -> Option<Box<dyn WorldGenerator>> {
+    let generator: Box<dyn WorldGenerator> = match name.to_ascii_lowercase().as_str() {
+        "flat" => Box::new(FlatWorldGenerator::new((height / 2).max(1), DIRT, GRASS, AIR)),
+        "classic" => Box::new(ClassicWorldGenerator::new(seed)),
+        "island" => Box::new(IslandWorldGenerator::new(IslandParameters { seed, ..Default::default() })),
+        "floatland" => Box::new(FloatlandWorldGenerator::new(FloatlandParameters { seed, ..Default::default() })),
+        "mountains" => Box::new(MountainWorldGenerator::new(MountainParameters { seed, ..Default::default() })),
+        "desert" => Box::new(DesertWorldGenerator::new(DesertParameters { seed, ..Default::default() })),
+        "empty" => Box::new(EmptyWorldGenerator::new(EmptyParameters { floor: Some(BEDROCK) })),
+        "void" => Box::new(EmptyWorldGenerator::new(EmptyParameters::default())),
+        _ => return None,
+    };
+    Some(generator)
+}
+
+fn index(x: usize, y: usize, z: usize, width: usize, length: usize) -> usize {
+    (y * length + z) * width + x
+}
+
+/// Size of the map being generated, in blocks.
+#[derive(Clone, Copy)]
+struct MapSize {
+    width: usize,
+    height: usize,
+    length: usize,
+}
+
+/// What a column of land is made of: stone, then `filler` for the
+/// three blocks under the surface, `top` at `surface`, and water
+/// above it up to `water_level`.
+struct Column {
+    surface: i32,
+    water_level: i32,
+    top: u8,
+    filler: u8,
+}
+
+/// Fills a column from the bottom. Anything outside the map is left out.
+fn fill_column(data: &mut [u8], size: MapSize, x: usize, z: usize, column: Column) {
+    for y in 0..size.height {
+        let yi = y as i32;
+        data[index(x, y, z, size.width, size.length)] = if yi < column.surface - 3 {
+            STONE
+        } else if yi < column.surface {
+            column.filler
+        } else if yi == column.surface {
+            column.top
+        } else if yi < column.water_level {
+            WATER
+        } else {
+            AIR
+        };
+    }
+}
+
+/// Samples octave noise scaled to roughly -1 to 1.
+fn sample(noise: &OctaveNoise, x: usize, z: usize, scale: f32) -> f32 {
+    (noise.sample(x as f32 * 1.3, z as f32 * 1.3) / scale).clamp(-1., 1.)
+}
+
+pub struct IslandParameters {
+    pub seed: i64,
+    /// Where the land meets the sea, as a fraction of the distance
+    /// from the centre to the edge of the map.
+    pub radius: f32,
+    /// Blocks above the water at the middle of the island.
+    pub peak: f32,
+    /// Blocks below the water at the sea floor.
+    pub depth: f32,
+}
+impl Default for IslandParameters {
+    fn default() -> Self {
+        Self { seed: 0, radius: 0.8, peak: 12., depth: 8. }
+    }
+}
+
+/// One island in the middle of a sea, falling away radially.
+pub struct IslandWorldGenerator {
+    parameters: IslandParameters,
+}
+impl IslandWorldGenerator {
+    pub fn new(parameters: IslandParameters) -> Self {
+        Self { parameters }
+    }
+}
+impl WorldGenerator for IslandWorldGenerator {
+    fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize) {
+        let p = &self.parameters;
+        let noise = OctaveNoise::new(&mut JavaRandom::new(p.seed), 8);
+        let water_level = height as i32 / 2;
+        let (half_width, half_length) = (width as f32 / 2., length as f32 / 2.);
+        for z in 0..length {
+            for x in 0..width {
+                let dx = (x as f32 + 0.5 - half_width) / half_width;
+                let dz = (z as f32 + 0.5 - half_length) / half_length;
+                let distance = (dx * dx + dz * dz).sqrt() / p.radius.max(f32::EPSILON);
+                // Smoothstep, so the island is flat on top and at the sea floor.
+                let falloff = (1. - distance).clamp(0., 1.);
+                let falloff = falloff * falloff * (3. - 2. * falloff);
+                let bumps = sample(&noise, x, z, 32.) * 4.;
+                let surface = water_level as f32 - p.depth + falloff * (p.depth + p.peak + bumps);
+                let surface = surface as i32;
+                let (top, filler) = if surface < water_level { (SAND, SAND) } else { (GRASS, DIRT) };
+                fill_column(data, MapSize { width, height, length }, x, z, Column { surface, water_level, top, filler });
+            }
+        }
+    }
+}
+
+pub struct FloatlandParameters {
+    pub seed: i64,
+    /// Roughly how much of the sky is covered by land, from 0 to 1.
+    pub coverage: f32,
+    /// How far the thickest land hangs below its surface.
+    pub thickness: f32,
+}
+impl Default for FloatlandParameters {
+    fn default() -> Self {
+        Self { seed: 0, coverage: 0.35, thickness: 12. }
+    }
+}
+
+/// Islands floating over a void, with hanging undersides.
+pub struct FloatlandWorldGenerator {
+    parameters: FloatlandParameters,
+}
+impl FloatlandWorldGenerator {
+    pub fn new(parameters: FloatlandParameters) -> Self {
+        Self { parameters }
+    }
+}
+impl WorldGenerator for FloatlandWorldGenerator {
+    fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize) {
+        let p = &self.parameters;
+        let mut random = JavaRandom::new(p.seed);
+        let shape = OctaveNoise::new(&mut random, 8);
+        let altitude = OctaveNoise::new(&mut random, 6);
+        let threshold = 1. - 2. * p.coverage.clamp(0., 1.);
+        let middle = height as f32 * 2. / 3.;
+        data.fill(AIR);
+        for z in 0..length {
+            for x in 0..width {
+                let value = sample(&shape, x, z, 32.);
+                if value <= threshold {
+                    continue;
+                }
+                let solidity = ((value - threshold) / (1. - threshold).max(f32::EPSILON)).min(1.);
+                let center = middle + sample(&altitude, x, z, 16.) * 6.;
+                let top = (center + solidity * p.thickness * 0.25) as i32;
+                let bottom = (center - solidity * p.thickness) as i32;
+                for y in bottom.max(0)..=top.min(height as i32 - 1) {
+                    data[index(x, y as usize, z, width, length)] = if y == top {
+                        GRASS
+                    } else if y >= top - 3 {
+                        DIRT
+                    } else {
+                        STONE
+                    };
+                }
+            }
+        }
+    }
+}
+
+pub struct MountainParameters {
+    pub seed: i64,
+    /// Blocks above the water at the highest peaks.
+    pub peak: f32,
+    /// Blocks above the water past which mountains are bare stone.
+    pub rock_line: i32,
+}
+impl Default for MountainParameters {
+    fn default() -> Self {
+        Self { seed: 0, peak: 40., rock_line: 20 }
+    }
+}
+
+/// Steep ridges with rocky tops and lakes in the valleys.
+pub struct MountainWorldGenerator {
+    parameters: MountainParameters,
+}
+impl MountainWorldGenerator {
+    pub fn new(parameters: MountainParameters) -> Self {
+        Self { parameters }
+    }
+}
+impl WorldGenerator for MountainWorldGenerator {
+    fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize) {
+        let p = &self.parameters;
+        let noise = OctaveNoise::new(&mut JavaRandom::new(p.seed), 8);
+        let water_level = height as i32 / 4;
+        for z in 0..length {
+            for x in 0..width {
+                // Folding the noise around zero turns its slopes into ridges.
+                let ridge = 1. - sample(&noise, x, z, 48.).abs();
+                let surface = water_level - 3 + (ridge * ridge * ridge * (p.peak + 3.)) as i32;
+                let surface = surface.min(height as i32 - 1);
+                let (top, filler) = if surface > water_level + p.rock_line {
+                    (STONE, STONE)
+                } else if surface < water_level {
+                    (SAND, DIRT)
+                } else {
+                    (GRASS, DIRT)
+                };
+                fill_column(data, MapSize { width, height, length }, x, z, Column { surface, water_level, top, filler });
+            }
+        }
+    }
+}
+
+pub struct DesertParameters {
+    pub seed: i64,
+    /// Blocks between the lowest and highest dunes.
+    pub dune_height: f32,
+}
+impl Default for DesertParameters {
+    fn default() -> Self {
+        Self { seed: 0, dune_height: 6. }
+    }
+}
+
+/// Rolling sand dunes over sandstone, with no water.
+pub struct DesertWorldGenerator {
+    parameters: DesertParameters,
+}
+impl DesertWorldGenerator {
+    pub fn new(parameters: DesertParameters) -> Self {
+        Self { parameters }
+    }
+}
+impl WorldGenerator for DesertWorldGenerator {
+    fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize) {
+        let p = &self.parameters;
+        let noise = OctaveNoise::new(&mut JavaRandom::new(p.seed), 6);
+        let ground = height as i32 / 2;
+        for z in 0..length {
+            for x in 0..width {
+                let dune = (sample(&noise, x, z, 16.) + 1.) / 2.;
+                let surface = (ground + (dune * p.dune_height) as i32).min(height as i32 - 1);
+                for y in 0..height {
+                    let yi = y as i32;
+                    data[index(x, y, z, width, length)] = if yi > surface {
+                        AIR
+                    } else if yi > surface - 4 {
+                        SAND
+                    } else if yi > surface - 8 {
+                        SANDSTONE
+                    } else {
+                        STONE
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EmptyParameters {
+    /// Block for the bottom layer, or `None` to leave the map all air.
+    pub floor: Option<u8>,
+}
+
+/// An empty map, for building from scratch.
+pub struct EmptyWorldGenerator {
+    parameters: EmptyParameters,
+}
+impl EmptyWorldGenerator {
+    pub fn new(parameters: EmptyParameters) -> Self {
+        Self { parameters }
+    }
+}
+impl WorldGenerator for EmptyWorldGenerator {
+    fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize) {
+        data.fill(AIR);
+        if width == 0 || height == 0 || length == 0 {
+            return;
+        }
+        if let Some(floor) = self.parameters.floor {
+            data[..width * length].fill(floor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::{BlockPosition, World};
+
+    fn world(name: &str) -> World {
+        World::new(theme(name, 5, 64).unwrap(), 96, 64, 96)
+    }
+
+    #[test]
+    fn every_theme_generates() {
+        for name in THEMES {
+            let world = world(name);
+            assert_eq!(world.data.read().unwrap().lower, self::world(name).data.read().unwrap().lower, "{}", name);
+        }
+        assert!(theme("lava lake", 0, 64).is_none());
+
+        // Flat ground fills the bottom half, with grass on top.
+        let flat = world("flat");
+        assert_eq!(flat.get(BlockPosition::new(40, 31, 40)), Some(GRASS as u16));
+        assert_eq!(flat.get(BlockPosition::new(40, 30, 40)), Some(DIRT as u16));
+        assert_eq!(flat.get(BlockPosition::new(40, 32, 40)), Some(AIR as u16));
+        assert!(world("classic").column_height(48, 48).is_some());
+        assert!(world("mountains").column_height(48, 48).is_some());
+        let empty = world("empty");
+        let void = world("void");
+        for (x, z) in [(0, 0), (95, 95), (30, 60)] {
+            assert_eq!(empty.get(BlockPosition::new(x, 0, z)), Some(BEDROCK as u16));
+            assert_eq!(empty.column_height(x, z), Some(0));
+            assert_eq!(void.column_height(x, z), None);
+        }
+        assert!(void.data.read().unwrap().lower.iter().all(|&block| block == AIR));
+    }
+
+    #[test]
+    fn zero_sized_maps_generate() {
+        for name in THEMES {
+            World::new(theme(name, 5, 0).unwrap(), 16, 0, 16);
+            World::new(theme(name, 5, 16).unwrap(), 0, 16, 16);
+        }
+    }
+
+    #[test]
+    fn island_is_surrounded_by_sea() {
+        let world = world("island");
+        assert!(world.column_height(48, 48).unwrap() >= 32);
+        for (x, z) in [(0, 0), (95, 0), (0, 95), (95, 95)] {
+            assert_eq!(world.get(BlockPosition::new(x, 31, z)), Some(WATER as u16));
+        }
+    }
+
+    #[test]
+    fn floatland_and_void_have_no_ground() {
+        for name in ["floatland", "void"] {
+            let world = world(name);
+            assert_eq!(world.get(BlockPosition::new(10, 0, 10)), Some(AIR as u16));
+        }
+        let floatland = world("floatland");
+        let land = (0..96).flat_map(|x| (0..96).map(move |z| (x, z))).filter(|&(x, z)| floatland.column_height(x, z).is_some()).count();
+        assert!(land > 0 && land < 96 * 96);
+        assert_eq!(world("empty").get(BlockPosition::new(10, 0, 10)), Some(BEDROCK as u16));
+    }
+
+    #[test]
+    fn desert_is_sand() {
+        let world = world("desert");
+        let y = world.column_height(20, 30).unwrap();
+        assert_eq!(world.get(BlockPosition::new(20, y, 30)), Some(SAND as u16));
+    }
+}
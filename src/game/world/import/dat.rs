@@ -0,0 +1,400 @@
+//! Classic 0.0.24a and later levels, which are a Java serialized
+//! `com.mojang.minecraft.level.Level`. Only as much of Java's
+//! serialization format is understood as is needed to walk
+//! past every object and pick out the level's fields.
+
+use std::rc::Rc;
+
+use crate::game::{camera::angle_to_byte, world::{cw::{ClassicWorld, WorldSpawn}, BlockPosition, World}};
+
+use super::{ImportError, Reader};
+
+const FORMAT: &str = ".dat";
+
+const STREAM_MAGIC: u16 = 0xACED;
+const STREAM_VERSION: u16 = 5;
+/// The first handle given out in a stream.
+const BASE_HANDLE: i32 = 0x7E0000;
+
+const TC_NULL: u8 = 0x70;
+const TC_REFERENCE: u8 = 0x71;
+const TC_CLASSDESC: u8 = 0x72;
+const TC_OBJECT: u8 = 0x73;
+const TC_STRING: u8 = 0x74;
+const TC_ARRAY: u8 = 0x75;
+const TC_CLASS: u8 = 0x76;
+const TC_BLOCKDATA: u8 = 0x77;
+const TC_ENDBLOCKDATA: u8 = 0x78;
+const TC_RESET: u8 = 0x79;
+const TC_BLOCKDATALONG: u8 = 0x7A;
+const TC_LONGSTRING: u8 = 0x7C;
+const TC_PROXYCLASSDESC: u8 = 0x7D;
+const TC_ENUM: u8 = 0x7E;
+
+/// The class has a `writeObject` method, so its
+/// fields are followed by extra annotation data.
+const SC_WRITE_METHOD: u8 = 0x01;
+const SC_EXTERNALIZABLE: u8 = 0x04;
+const SC_BLOCK_DATA: u8 = 0x08;
+
+struct ClassDesc {
+    name: String,
+    flags: u8,
+    /// Type code and name of each field.
+    fields: Vec<(u8, String)>,
+    super_class: Option<Rc<ClassDesc>>,
+}
+
+#[derive(Clone)]
+enum Value {
+    Null,
+    /// Any integer, char or boolean.
+    Int(i64),
+    Float(f64),
+    String(Rc<str>),
+    Bytes(Rc<[u8]>),
+    /// Any other array. The level doesn't need their elements.
+    Array,
+    Object(Rc<Object>),
+    Class(Rc<ClassDesc>),
+}
+
+struct Object {
+    /// Field values, superclass fields first.
+    fields: Vec<(String, Value)>,
+}
+impl Object {
+    fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
+/// Reads a Java object stream.
+struct Deserializer<'a> {
+    reader: Reader<'a>,
+    /// Values by handle. Objects still being read are `None`.
+    handles: Vec<Option<Value>>,
+}
+impl<'a> Deserializer<'a> {
+    fn new(reader: Reader<'a>) -> Self {
+        Self { reader, handles: Vec::new() }
+    }
+    fn reserve_handle(&mut self) -> usize {
+        self.handles.push(None);
+        self.handles.len() - 1
+    }
+    fn new_handle(&mut self, value: Value) -> Value {
+        self.handles.push(Some(value.clone()));
+        value
+    }
+    fn reference(&mut self) -> Result<Value, ImportError> {
+        let handle = self.reader.i32()?.wrapping_sub(BASE_HANDLE);
+        match self.handles.get(handle as usize) {
+            Some(value) => Ok(value.clone().unwrap_or(Value::Null)),
+            None => Err(self.reader.error(format!("bad handle {:#x}", handle))),
+        }
+    }
+    /// Reads any value written with `writeObject`.
+    fn content(&mut self) -> Result<Value, ImportError> {
+        let code = self.reader.u8()?;
+        match code {
+            TC_NULL => Ok(Value::Null),
+            TC_REFERENCE => self.reference(),
+            TC_OBJECT => self.object(),
+            TC_STRING => {
+                let string = self.reader.utf()?;
+                Ok(self.new_handle(Value::String(string.into())))
+            }
+            TC_LONGSTRING => {
+                let length = self.reader.i64()?;
+                let bytes = self.reader.bytes(usize::try_from(length).map_err(|_| self.reader.error("bad string length"))?)?;
+                Ok(self.new_handle(Value::String(String::from_utf8_lossy(bytes).into())))
+            }
+            TC_ARRAY => self.array(),
+            TC_CLASS => {
+                let class = self.class_desc()?;
+                let value = class.map_or(Value::Null, Value::Class);
+                Ok(self.new_handle(value))
+            }
+            TC_ENUM => {
+                self.class_desc()?;
+                let handle = self.reserve_handle();
+                let name = self.content()?;
+                self.handles[handle] = Some(name.clone());
+                Ok(name)
+            }
+            TC_CLASSDESC | TC_PROXYCLASSDESC => {
+                let class = self.class_desc_body(code)?;
+                Ok(Value::Class(class))
+            }
+            TC_RESET => {
+                self.handles.clear();
+                self.content()
+            }
+            _ => Err(self.reader.error(format!("unexpected type code {:#x}", code))),
+        }
+    }
+    fn class_desc(&mut self) -> Result<Option<Rc<ClassDesc>>, ImportError> {
+        let code = self.reader.u8()?;
+        match code {
+            TC_NULL => Ok(None),
+            TC_REFERENCE => match self.reference()? {
+                Value::Class(class) => Ok(Some(class)),
+                _ => Err(self.reader.error("reference to a non-class where a class was expected")),
+            },
+            TC_CLASSDESC | TC_PROXYCLASSDESC => Ok(Some(self.class_desc_body(code)?)),
+            _ => Err(self.reader.error(format!("expected a class, found type code {:#x}", code))),
+        }
+    }
+    fn class_desc_body(&mut self, code: u8) -> Result<Rc<ClassDesc>, ImportError> {
+        let (name, handle, flags, fields) = if code == TC_PROXYCLASSDESC {
+            let handle = self.reserve_handle();
+            let count = self.reader.i32()?;
+            for _ in 0..count {
+                self.reader.utf()?;
+            }
+            (String::new(), handle, 0x02, Vec::new())
+        } else {
+            let name = self.reader.utf()?;
+            let _serial_version = self.reader.i64()?;
+            let handle = self.reserve_handle();
+            let flags = self.reader.u8()?;
+            let count = self.reader.u16()?;
+            let mut fields = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let code = self.reader.u8()?;
+                let name = self.reader.utf()?;
+                if code == b'[' || code == b'L' {
+                    // The field's class name, as a string.
+                    self.content()?;
+                }
+                fields.push((code, name));
+            }
+            (name, handle, flags, fields)
+        };
+        self.annotation()?;
+        let super_class = self.class_desc()?;
+        let class = Rc::new(ClassDesc { name, flags, fields, super_class });
+        self.handles[handle] = Some(Value::Class(class.clone()));
+        Ok(class)
+    }
+    /// Skips data written by a class's own
+    /// `writeObject`, up to `TC_ENDBLOCKDATA`.
+    fn annotation(&mut self) -> Result<(), ImportError> {
+        loop {
+            match self.reader.data.first().copied() {
+                Some(TC_ENDBLOCKDATA) => {
+                    self.reader.u8()?;
+                    return Ok(());
+                }
+                Some(TC_BLOCKDATA) => {
+                    self.reader.u8()?;
+                    let length = self.reader.u8()?;
+                    self.reader.bytes(length as usize)?;
+                }
+                Some(TC_BLOCKDATALONG) => {
+                    self.reader.u8()?;
+                    let length = self.reader.i32()?;
+                    self.reader.bytes(usize::try_from(length).map_err(|_| self.reader.error("bad block data length"))?)?;
+                }
+                _ => {
+                    self.content()?;
+                }
+            }
+        }
+    }
+    fn object(&mut self) -> Result<Value, ImportError> {
+        let class = self.class_desc()?.ok_or_else(|| self.reader.error("object without a class"))?;
+        let handle = self.reserve_handle();
+        let mut hierarchy = vec![class.clone()];
+        while let Some(parent) = hierarchy.last().unwrap().super_class.clone() {
+            hierarchy.push(parent);
+        }
+        let mut fields = Vec::new();
+        for class in hierarchy.iter().rev() {
+            if class.flags & SC_EXTERNALIZABLE != 0 {
+                if class.flags & SC_BLOCK_DATA == 0 {
+                    return Err(self.reader.error(format!("can't read externalizable class {}", class.name)));
+                }
+                self.annotation()?;
+                continue;
+            }
+            for (code, name) in &class.fields {
+                fields.push((name.clone(), self.field_value(*code)?));
+            }
+            if class.flags & SC_WRITE_METHOD != 0 {
+                self.annotation()?;
+            }
+        }
+        let object = Value::Object(Rc::new(Object { fields }));
+        self.handles[handle] = Some(object.clone());
+        Ok(object)
+    }
+    fn array(&mut self) -> Result<Value, ImportError> {
+        let class = self.class_desc()?.ok_or_else(|| self.reader.error("array without a class"))?;
+        let handle = self.reserve_handle();
+        let size = usize::try_from(self.reader.i32()?).map_err(|_| self.reader.error("negative array size"))?;
+        // Array classes are named "[" followed by their element type.
+        let code = class.name.as_bytes().get(1).copied().unwrap_or(b'L');
+        let array = if code == b'B' {
+            Value::Bytes(self.reader.bytes(size)?.into())
+        } else {
+            for _ in 0..size {
+                self.field_value(code)?;
+            }
+            Value::Array
+        };
+        self.handles[handle] = Some(array.clone());
+        Ok(array)
+    }
+    fn field_value(&mut self, code: u8) -> Result<Value, ImportError> {
+        let reader = &mut self.reader;
+        Ok(match code {
+            b'B' => Value::Int(reader.u8()? as i8 as i64),
+            b'Z' => Value::Int(reader.u8()? as i64),
+            b'C' => Value::Int(reader.u16()? as i64),
+            b'S' => Value::Int(reader.i16()? as i64),
+            b'I' => Value::Int(reader.i32()? as i64),
+            b'J' => Value::Int(reader.i64()?),
+            b'F' => Value::Float(f32::from_bits(reader.i32()? as u32) as f64),
+            b'D' => Value::Float(f64::from_bits(reader.i64()? as u64)),
+            b'[' | b'L' => self.content()?,
+            _ => return Err(reader.error(format!("unknown field type {:?}", code as char))),
+        })
+    }
+}
+
+pub fn read(data: &[u8]) -> Result<ClassicWorld, ImportError> {
+    let mut reader = Reader::new(data, FORMAT);
+    // Magic and version, already checked by `MapFormat::detect`.
+    reader.bytes(5)?;
+    if reader.u16()? != STREAM_MAGIC || reader.u16()? != STREAM_VERSION {
+        return Err(reader.error("not a Java object stream"));
+    }
+    let mut stream = Deserializer::new(reader);
+    let level = match stream.content()? {
+        Value::Object(level) => level,
+        _ => return Err(stream.reader.error("level isn't an object")),
+    };
+    let error = |message: &str| ImportError::Invalid(FORMAT, message.to_string());
+    let int = |name: &str| match level.field(name) {
+        Some(Value::Int(value)) => Ok(*value),
+        _ => Err(error(&format!("missing field {}", name))),
+    };
+    // Classic calls the Z axis height and the Y axis depth.
+    let (width, height, length) = (int("width")? as i16, int("depth")? as i16, int("height")? as i16);
+    let blocks = match level.field("blocks") {
+        Some(Value::Bytes(blocks)) => blocks.to_vec(),
+        _ => return Err(error("missing field blocks")),
+    };
+    let world = World::from_data(blocks, None, width, height, length).map_err(|e| error(&e.to_string()))?;
+    let mut level_data = ClassicWorld::new(world);
+    if let (Ok(x), Ok(y), Ok(z)) = (int("xSpawn"), int("ySpawn"), int("zSpawn")) {
+        let yaw = match level.field("rotSpawn") {
+            Some(Value::Float(degrees)) => angle_to_byte(*degrees as f32),
+            _ => 0,
+        };
+        level_data.spawn = WorldSpawn { position: BlockPosition::new(x as i32, y as i32, z as i32), yaw, pitch: 0 };
+    }
+    if let Some(Value::String(name)) = level.field("name") {
+        level_data.name = Some(name.to_string());
+    }
+    Ok(level_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::import::{read_map, CLASSIC_MAGIC};
+
+    fn utf(data: &mut Vec<u8>, text: &str) {
+        data.extend_from_slice(&(text.len() as u16).to_be_bytes());
+        data.extend_from_slice(text.as_bytes());
+    }
+
+    fn int(data: &mut Vec<u8>, value: i32) {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// A level like Classic writes, with an `ArrayList` holding
+    /// the level itself to exercise annotations and references.
+    fn level() -> Vec<u8> {
+        let mut data = CLASSIC_MAGIC.to_be_bytes().to_vec();
+        data.push(2);
+        data.extend_from_slice(&STREAM_MAGIC.to_be_bytes());
+        data.extend_from_slice(&STREAM_VERSION.to_be_bytes());
+
+        // Level class, handle 0.
+        data.extend_from_slice(&[TC_OBJECT, TC_CLASSDESC]);
+        utf(&mut data, "com.mojang.minecraft.level.Level");
+        data.extend_from_slice(&1i64.to_be_bytes());
+        data.push(0x02);
+        data.extend_from_slice(&10u16.to_be_bytes());
+        for name in ["depth", "height", "width", "xSpawn", "ySpawn", "zSpawn"] {
+            data.push(b'I');
+            utf(&mut data, name);
+        }
+        data.push(b'F');
+        utf(&mut data, "rotSpawn");
+        // Class name strings are handles 1, 2 and 3.
+        data.push(b'[');
+        utf(&mut data, "blocks");
+        data.push(TC_STRING);
+        utf(&mut data, "[B");
+        data.push(b'L');
+        utf(&mut data, "name");
+        data.push(TC_STRING);
+        utf(&mut data, "Ljava/lang/String;");
+        data.push(b'L');
+        utf(&mut data, "blockMap");
+        data.push(TC_STRING);
+        utf(&mut data, "Ljava/util/ArrayList;");
+        data.extend_from_slice(&[TC_ENDBLOCKDATA, TC_NULL]);
+
+        // The level object is handle 4.
+        for value in [4, 3, 2, 1, 3, 2] {
+            int(&mut data, value);
+        }
+        data.extend_from_slice(&90f32.to_bits().to_be_bytes());
+        // byte[] class is handle 5, the array 6.
+        data.extend_from_slice(&[TC_ARRAY, TC_CLASSDESC]);
+        utf(&mut data, "[B");
+        data.extend_from_slice(&2i64.to_be_bytes());
+        data.extend_from_slice(&[0x02, 0, 0, TC_ENDBLOCKDATA, TC_NULL]);
+        int(&mut data, 24);
+        data.extend((0..24).map(|i| if i < 6 { 1 } else { 0 }));
+        data.push(TC_STRING);
+        utf(&mut data, "Test level");
+
+        // ArrayList class is handle 8, the list 9.
+        data.extend_from_slice(&[TC_OBJECT, TC_CLASSDESC]);
+        utf(&mut data, "java.util.ArrayList");
+        data.extend_from_slice(&3i64.to_be_bytes());
+        data.push(0x02 | SC_WRITE_METHOD);
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.push(b'I');
+        utf(&mut data, "size");
+        data.extend_from_slice(&[TC_ENDBLOCKDATA, TC_NULL]);
+        int(&mut data, 1);
+        data.extend_from_slice(&[TC_BLOCKDATA, 4, 0, 0, 0, 1, TC_REFERENCE]);
+        int(&mut data, BASE_HANDLE + 4);
+        data.push(TC_ENDBLOCKDATA);
+        data
+    }
+
+    #[test]
+    fn reads_serialized_level() {
+        let level = read_map(&level()).unwrap();
+        assert_eq!(level.name.as_deref(), Some("Test level"));
+        assert_eq!((level.world.width(), level.world.height(), level.world.length()), (2, 4, 3));
+        assert_eq!(level.world.get(BlockPosition::new(1, 0, 2)), Some(1));
+        assert_eq!(level.world.get(BlockPosition::new(1, 1, 2)), Some(0));
+        assert_eq!(level.spawn, WorldSpawn { position: BlockPosition::new(1, 3, 2), yaw: 64, pitch: 0 });
+    }
+
+    #[test]
+    fn rejects_truncated_level() {
+        let data = level();
+        assert!(read_map(&data[..data.len() - 10]).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! MCSharp and MCGalaxy levels: a little-endian header, the
+//! block array, then optional extra sections.
+
+use crate::game::world::{cw::{ClassicWorld, WorldSpawn}, BlockPosition, World};
+
+use super::{ImportError, Reader};
+
+const FORMAT: &str = ".lvl";
+
+/// First two bytes of a level, little-endian.
+pub const MAGIC: u16 = 1874;
+/// Starts the section holding the real IDs of `CUSTOM_BLOCK`s.
+const CUSTOM_BLOCKS_SECTION: u8 = 0xBD;
+/// Placeholder for a custom block, whose ID is in the custom blocks section.
+const CUSTOM_BLOCK: u8 = 163;
+
+/// Turns MCGalaxy's own blocks into ones the client can draw. Its
+/// op_ blocks become their ordinary versions, and other physics
+/// and special blocks past the CPE range are shown as stone.
+fn convert_block(block: u8) -> u8 {
+    match block {
+        0..=65 | CUSTOM_BLOCK => block,
+        100 => 20,
+        101 => 49,
+        102 => 45,
+        103 => 1,
+        104 => 4,
+        105 => 0,
+        106 => 8,
+        107 => 10,
+        _ => 1,
+    }
+}
+
+pub fn read(data: &[u8]) -> Result<ClassicWorld, ImportError> {
+    let mut reader = Reader::new(data, FORMAT);
+    reader.u16_le()?;
+    let width = reader.u16_le()? as i16;
+    let length = reader.u16_le()? as i16;
+    let height = reader.u16_le()? as i16;
+    let spawn = [reader.u16_le()?, reader.u16_le()?, reader.u16_le()?];
+    let (yaw, pitch) = (reader.u8()?, reader.u8()?);
+    // Visit and build permissions.
+    reader.bytes(2)?;
+
+    let volume = width.max(0) as usize * height.max(0) as usize * length.max(0) as usize;
+    let mut blocks = reader.bytes(volume)?.iter().map(|&b| convert_block(b)).collect::<Vec<u8>>();
+    if reader.data.first() == Some(&CUSTOM_BLOCKS_SECTION) {
+        reader.u8()?;
+        read_custom_blocks(&mut reader, &mut blocks, width as usize, height as usize, length as usize)?;
+    }
+    let world = World::from_data(blocks, None, width, height, length).map_err(|e| reader.error(e.to_string()))?;
+    let mut level = ClassicWorld::new(world);
+    level.spawn = WorldSpawn {
+        position: BlockPosition::new(spawn[0] as i32, spawn[2] as i32, spawn[1] as i32),
+        yaw,
+        pitch,
+    };
+    Ok(level)
+}
+
+/// Replaces each `CUSTOM_BLOCK` with its real ID. Custom IDs are
+/// stored for every 16x16x16 chunk holding any, in Y, Z, X order.
+fn read_custom_blocks(reader: &mut Reader, blocks: &mut [u8], width: usize, height: usize, length: usize) -> Result<(), ImportError> {
+    for cy in (0..height).step_by(16) {
+        for cz in (0..length).step_by(16) {
+            for cx in (0..width).step_by(16) {
+                if reader.u8()? != 1 {
+                    continue;
+                }
+                let chunk = reader.bytes(16 * 16 * 16)?;
+                for (i, &custom) in chunk.iter().enumerate() {
+                    let (x, z, y) = (cx + (i & 0xF), cz + ((i >> 4) & 0xF), cy + (i >> 8));
+                    if x >= width || y >= height || z >= length {
+                        continue;
+                    }
+                    let index = (y * length + z) * width + x;
+                    if blocks[index] == CUSTOM_BLOCK {
+                        blocks[index] = custom;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::import::read_map;
+
+    fn header(width: u16, length: u16, height: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [MAGIC, width, length, height, 1, 2, 3] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[64, 32, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn reads_header_and_blocks() {
+        let mut data = header(2, 3, 4);
+        data.extend((0..24).map(|i| if i == 1 { 103 } else { 2 }));
+        let level = read_map(&data).unwrap();
+        assert_eq!((level.world.width(), level.world.height(), level.world.length()), (2, 4, 3));
+        assert_eq!(level.spawn, WorldSpawn { position: BlockPosition::new(1, 3, 2), yaw: 64, pitch: 32 });
+        assert_eq!(level.world.get(BlockPosition::new(1, 0, 0)), Some(1));
+        assert_eq!(level.world.get(BlockPosition::new(0, 3, 2)), Some(2));
+
+        data.pop();
+        assert!(read_map(&data).is_err());
+    }
+
+    #[test]
+    fn replaces_custom_blocks() {
+        let mut data = header(2, 2, 2);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, CUSTOM_BLOCK]);
+        data.push(CUSTOM_BLOCKS_SECTION);
+        data.push(1);
+        let mut chunk = vec![0; 16 * 16 * 16];
+        chunk[(16 + 1) * 16 + 1] = 90;
+        data.extend_from_slice(&chunk);
+        let level = read_map(&data).unwrap();
+        assert_eq!(level.world.get(BlockPosition::new(1, 1, 1)), Some(90));
+    }
+}
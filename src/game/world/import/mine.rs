@@ -0,0 +1,57 @@
+//! Classic 0.0.13a to 0.0.23a levels: a small header
+//! followed by the raw block array.
+
+use crate::game::world::{cw::ClassicWorld, World};
+
+use super::{default_spawn, ImportError, Reader};
+
+const FORMAT: &str = ".mine";
+
+pub fn read(data: &[u8]) -> Result<ClassicWorld, ImportError> {
+    let mut reader = Reader::new(data, FORMAT);
+    // Magic and version, already checked by `MapFormat::detect`.
+    reader.bytes(5)?;
+    let name = reader.utf()?;
+    let _creator = reader.utf()?;
+    let _created = reader.i64()?;
+    // Classic calls the Z axis height and the Y axis depth.
+    let width = reader.i16()?;
+    let length = reader.i16()?;
+    let height = reader.i16()?;
+    let blocks = reader.rest().to_vec();
+    let world = World::from_data(blocks, None, width, height, length).map_err(|e| reader.error(e.to_string()))?;
+    let mut level = ClassicWorld::new(world);
+    level.spawn = default_spawn(&level.world);
+    level.name = Some(name);
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::world::{BlockPosition, import::{read_map, CLASSIC_MAGIC}};
+
+    #[test]
+    fn reads_header_and_blocks() {
+        let mut data = CLASSIC_MAGIC.to_be_bytes().to_vec();
+        data.push(1);
+        for text in ["A level", "notch"] {
+            data.extend_from_slice(&(text.len() as u16).to_be_bytes());
+            data.extend_from_slice(text.as_bytes());
+        }
+        data.extend_from_slice(&0i64.to_be_bytes());
+        for size in [2i16, 3, 4] {
+            data.extend_from_slice(&size.to_be_bytes());
+        }
+        // 2 wide, 3 long and 4 high, with stone at the bottom.
+        data.extend((0..24).map(|i| if i < 6 { 1 } else { 0 }));
+        let level = read_map(&data).unwrap();
+        assert_eq!(level.name.as_deref(), Some("A level"));
+        assert_eq!((level.world.width(), level.world.height(), level.world.length()), (2, 4, 3));
+        assert_eq!(level.world.get(BlockPosition::new(1, 0, 2)), Some(1));
+        assert_eq!(level.world.get(BlockPosition::new(0, 1, 0)), Some(0));
+        assert_eq!(level.spawn.position, BlockPosition::new(1, 1, 1));
+
+        data.pop();
+        assert!(read_map(&data).is_err());
+    }
+}
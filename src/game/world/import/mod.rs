@@ -0,0 +1,183 @@
+//! Loading maps in any format the client understands. Every
+//! format is gzipped, and told apart by its first bytes.
+
+use std::{fs::File, io::{self, Read}, path::Path};
+
+use flate2::read::GzDecoder;
+use nbt::decode::read_compound_tag;
+use thiserror::Error;
+
+use super::{cw::{ClassicWorld, ClassicWorldError, WorldSpawn}, schematic::{Schematic, SchematicError}, BlockPosition, World};
+
+mod dat;
+mod lvl;
+mod mine;
+
+/// Starts Classic's .mine and .dat levels, before their version byte.
+const CLASSIC_MAGIC: u32 = 0x271BB788;
+
+/// Error when importing a map.
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    ClassicWorld(#[from] ClassicWorldError),
+    #[error(transparent)]
+    Schematic(#[from] SchematicError),
+    #[error("unrecognised map format")]
+    UnknownFormat,
+    #[error("invalid {0} map: {1}")]
+    Invalid(&'static str, String),
+}
+
+/// A map file format.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapFormat {
+    /// ClassiCube's NBT format.
+    ClassicWorld,
+    /// Classic 0.0.13a to 0.0.23a levels.
+    Mine,
+    /// Later Classic levels, in Java serialization.
+    Dat,
+    /// MCSharp and MCGalaxy levels.
+    Lvl,
+    /// MCEdit schematics, loaded as a world of their own size.
+    Schematic,
+}
+impl MapFormat {
+    /// Works out the format of a decompressed map from its first bytes.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.len() >= 5 && u32::from_be_bytes([data[0], data[1], data[2], data[3]]) == CLASSIC_MAGIC {
+            return match data[4] {
+                1 => Some(MapFormat::Mine),
+                2 => Some(MapFormat::Dat),
+                _ => None,
+            };
+        }
+        if data.len() >= 2 && u16::from_le_bytes([data[0], data[1]]) == lvl::MAGIC {
+            return Some(MapFormat::Lvl);
+        }
+        // An NBT compound, told apart by its name.
+        if data.len() >= 3 && data[0] == 0x0A {
+            let name = &data[3..];
+            if name.starts_with(b"ClassicWorld") {
+                return Some(MapFormat::ClassicWorld);
+            }
+            if name.starts_with(b"Schematic") {
+                return Some(MapFormat::Schematic);
+            }
+        }
+        None
+    }
+}
+
+/// Loads a map in any supported format.
+pub fn load_map(path: impl AsRef<Path>) -> Result<ClassicWorld, ImportError> {
+    let mut data = Vec::new();
+    GzDecoder::new(File::open(path)?).read_to_end(&mut data)?;
+    read_map(&data)
+}
+
+/// Reads a decompressed map in any supported format.
+pub fn read_map(data: &[u8]) -> Result<ClassicWorld, ImportError> {
+    match MapFormat::detect(data).ok_or(ImportError::UnknownFormat)? {
+        MapFormat::ClassicWorld => {
+            let root = read_compound_tag(&mut &data[..]).map_err(ClassicWorldError::from)?;
+            Ok(ClassicWorld::from_tag(&root)?)
+        }
+        MapFormat::Mine => mine::read(data),
+        MapFormat::Dat => dat::read(data),
+        MapFormat::Lvl => lvl::read(data),
+        MapFormat::Schematic => {
+            let root = read_compound_tag(&mut &data[..]).map_err(SchematicError::from)?;
+            let world = Schematic::from_tag(&root)?.to_world();
+            let mut level = ClassicWorld::new(world);
+            level.spawn = default_spawn(&level.world);
+            Ok(level)
+        }
+    }
+}
+
+/// Reads big-endian values from the start of a buffer.
+struct Reader<'a> {
+    data: &'a [u8],
+    format: &'static str,
+}
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], format: &'static str) -> Self {
+        Self { data, format }
+    }
+    fn error(&self, message: impl Into<String>) -> ImportError {
+        ImportError::Invalid(self.format, message.into())
+    }
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], ImportError> {
+        if self.data.len() < count {
+            return Err(self.error("unexpected end of file"));
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+    /// Everything left unread.
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ImportError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+    fn u8(&mut self) -> Result<u8, ImportError> {
+        Ok(self.array::<1>()?[0])
+    }
+    fn u16(&mut self) -> Result<u16, ImportError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+    fn u16_le(&mut self) -> Result<u16, ImportError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    fn i16(&mut self) -> Result<i16, ImportError> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+    fn i32(&mut self) -> Result<i32, ImportError> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+    fn i64(&mut self) -> Result<i64, ImportError> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+    /// A Java modified UTF-8 string with a 16 bit length.
+    fn utf(&mut self) -> Result<String, ImportError> {
+        let length = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+    }
+}
+
+/// Where Classic puts players in maps without a spawn: the
+/// middle of the map, standing on its highest block.
+fn default_spawn(world: &World) -> WorldSpawn {
+    let (x, z) = (world.width() as i32 / 2, world.length() as i32 / 2);
+    let y = world.column_height(x, z).map_or(world.height() as i32, |y| y + 1);
+    WorldSpawn { position: BlockPosition::new(x, y, z), yaw: 0, pitch: 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(MapFormat::detect(&[0x27, 0x1B, 0xB7, 0x88, 1]), Some(MapFormat::Mine));
+        assert_eq!(MapFormat::detect(&[0x27, 0x1B, 0xB7, 0x88, 2]), Some(MapFormat::Dat));
+        assert_eq!(MapFormat::detect(&[0x27, 0x1B, 0xB7, 0x88, 3]), None);
+        assert_eq!(MapFormat::detect(&1874u16.to_le_bytes()), Some(MapFormat::Lvl));
+        let mut nbt = vec![0x0A, 0, 12];
+        nbt.extend_from_slice(b"ClassicWorld");
+        assert_eq!(MapFormat::detect(&nbt), Some(MapFormat::ClassicWorld));
+        let mut nbt = vec![0x0A, 0, 9];
+        nbt.extend_from_slice(b"Schematic");
+        assert_eq!(MapFormat::detect(&nbt), Some(MapFormat::Schematic));
+        assert_eq!(MapFormat::detect(b"hello"), None);
+        assert!(matches!(read_map(b""), Err(ImportError::UnknownFormat)));
+    }
+}
@@ -1,9 +1,41 @@
-use std::{io::Write, sync::{RwLock, Arc, RwLockReadGuard}};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+
+use ahash::AHashSet;
+
+use anyhow::{anyhow, bail};
 
 use enum_iterator::IntoEnumIterator;
+use glam::Vec3;
 use num_derive::{FromPrimitive, ToPrimitive};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub mod classic;
+pub mod cw;
+pub mod generators;
+pub mod import;
+pub mod noise;
+pub mod raycast;
+pub mod schematic;
+
+/// A block type. Plain Classic only uses the lower
+/// 8 bits; ExtendedBlocks widens this to 10.
+pub type BlockId = u16;
+
+/// Length of a block's edge in render space.
+pub const BLOCK_SIZE: f32 = 0.5;
+
+/// Converts a point in block space (where block `(x, y, z)`
+/// spans `x..x + 1`) to render space, where blocks are centered
+/// on their position multiplied by `BLOCK_SIZE`.
+pub fn block_to_render(v: Vec3) -> Vec3 {
+    (v - Vec3::splat(0.5)) * BLOCK_SIZE
+}
+
+/// Converts a point in render space back to block space.
+pub fn render_to_block(v: Vec3) -> Vec3 {
+    v / BLOCK_SIZE + Vec3::splat(0.5)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct ChunkPosition {
     pub x: usize,
     pub y: usize,
@@ -15,7 +47,7 @@ impl ChunkPosition {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct BlockPosition {
     pub x: i32,
     pub y: i32,
@@ -50,127 +82,260 @@ pub enum Facing {
     Right,
     Left,
 }
+impl Facing {
+    /// The face ID used by PlayerClick.
+    pub fn click_face(&self) -> u8 {
+        match self {
+            Facing::Left => 0,
+            Facing::Right => 1,
+            Facing::Top => 2,
+            Facing::Bottom => 3,
+            Facing::Back => 4,
+            Facing::Front => 5,
+        }
+    }
+}
+/// Names of the Classic and CustomBlocks blocks, by ID.
+const BLOCK_NAMES: [&str; 66] = [
+    "Air", "Stone", "Grass", "Dirt", "Cobblestone", "Wood", "Sapling", "Bedrock",
+    "Water", "Still water", "Lava", "Still lava", "Sand", "Gravel", "Gold ore", "Iron ore",
+    "Coal ore", "Log", "Leaves", "Sponge", "Glass", "Red", "Orange", "Yellow",
+    "Lime", "Green", "Teal", "Aqua", "Cyan", "Blue", "Indigo", "Violet",
+    "Magenta", "Pink", "Black", "Gray", "White", "Dandelion", "Rose", "Brown mushroom",
+    "Red mushroom", "Gold", "Iron", "Double slab", "Slab", "Brick", "TNT", "Bookshelf",
+    "Mossy rocks", "Obsidian", "Cobblestone slab", "Rope", "Sandstone", "Snow", "Fire", "Light pink",
+    "Forest green", "Brown", "Deep blue", "Turquoise", "Ice", "Ceramic tile", "Magma", "Pillar",
+    "Crate", "Stone brick",
+];
+
+/// Returns the display name of a block.
+pub fn block_name(id: BlockId) -> &'static str {
+    BLOCK_NAMES.get(id as usize).copied().unwrap_or("Unknown")
+}
+
+/// How a block stops things moving through it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockCollision {
+    /// Air, plants and fire.
+    Gas,
+    /// Water and lava.
+    Liquid,
+    Solid,
+}
+
+/// Returns how a block collides.
+pub fn block_collision(id: BlockId) -> BlockCollision {
+    match id {
+        0 | 6 | 37..=40 | 51 | 54 => BlockCollision::Gas,
+        8..=11 => BlockCollision::Liquid,
+        _ => BlockCollision::Solid,
+    }
+}
+
+/// Whether a block stops light and weather. Liquids do, as
+/// in Classic; see-through blocks like glass and leaves don't.
+pub fn block_is_opaque(id: BlockId) -> bool {
+    block_collision(id) != BlockCollision::Gas && !matches!(id, 18 | 20 | 60)
+}
+
 #[derive(Clone, Copy)]
 pub struct Block {
-    pub id: u8,
+    pub id: BlockId,
     pub position: BlockPosition,
 }
 impl Block {
-    pub fn new(id: u8, position: BlockPosition) -> Self {
+    pub fn new(id: BlockId, position: BlockPosition) -> Self {
         Self {
             id,
             position
         }
     }
 }
+/// Block storage for a world. The upper bits of extended
+/// block IDs live in a second array which is only allocated
+/// once needed, so Classic worlds keep one byte per block.
+pub struct BlockArray {
+    pub lower: Box<[u8]>, // XZY
+    pub upper: Option<Box<[u8]>>,
+}
+impl BlockArray {
+    pub fn new(lower: Box<[u8]>) -> Self {
+        Self { lower, upper: None }
+    }
+    pub fn get(&self, index: usize) -> Option<BlockId> {
+        let lower = *self.lower.get(index)? as BlockId;
+        let upper = self.upper.as_ref().and_then(|u| u.get(index)).copied().unwrap_or(0) as BlockId;
+        Some(lower | upper << 8)
+    }
+    pub fn set(&mut self, index: usize, id: BlockId) {
+        self.lower[index] = id as u8;
+        let upper = (id >> 8) as u8;
+        if upper != 0 && self.upper.is_none() {
+            self.upper = Some(vec![0; self.lower.len()].into_boxed_slice());
+        }
+        if let Some(u) = self.upper.as_mut() {
+            u[index] = upper;
+        }
+    }
+}
+
+/// Removes the block count from the start of level
+/// data, checking it matches the blocks that follow.
+pub fn strip_length_prefix(data: &[u8]) -> anyhow::Result<&[u8]> {
+    if data.len() < 4 {
+        bail!("level data too short for its length prefix");
+    }
+    let (prefix, blocks) = data.split_at(4);
+    let count = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+    if count != blocks.len() {
+        bail!("level data has {} blocks, but its prefix says {}", blocks.len(), count);
+    }
+    Ok(blocks)
+}
+
 #[derive(Clone)]
 pub struct World {
-    pub data: Arc<RwLock<Box<[u8]>>>, // XZY
+    /// Blocks, only reached through the bounds-checked
+    /// methods outside of the world's own modules.
+    data: Arc<RwLock<BlockArray>>,
+    /// Chunks whose meshes are out of date, shared between clones.
+    dirty: Arc<Mutex<AHashSet<ChunkPosition>>>,
     width: usize,
     height: usize,
     length: usize,
 }
 
 impl World {
-    pub fn from_data(data: Vec<u8>, width: i16, height: i16, length: i16) -> Self {
-        Self {
-            data: Arc::new(RwLock::new(data.into_boxed_slice())),
-            width: width as usize,
-            height: height as usize,
-            length: length as usize,
-        }
-    }
-    pub fn from_file(file_path: &str) -> Option<World> {
-        use flate2::read::GzDecoder;
-        use nbt::decode::read_compound_tag;
-        let cursor = std::fs::File::open(file_path).ok()?;
-        let mut cursor = GzDecoder::new(cursor);
-        let root_tag = read_compound_tag(&mut cursor).ok()?;
-        let mut world = root_tag.get_i8_vec("BlockArray").ok()?.to_owned();
-        let width = root_tag.get_i16("X").ok()? as usize;
-        let height = root_tag.get_i16("Y").ok()? as usize;
-        let length = root_tag.get_i16("Z").ok()? as usize;
-        let spawn = root_tag.get_compound_tag("Spawn").ok()?;
-        let spawn_x = spawn.get_i16("X").ok()?;
-        let spawn_y = spawn.get_i16("Y").ok()?;
-        let spawn_z = spawn.get_i16("Z").ok()?;
-        let mut newworld: Vec<u8> = vec![];
-        let mut world = std::mem::ManuallyDrop::new(world);
-        unsafe {
-            let ptr = world.as_mut_ptr();
-            let len = world.len();
-            let cap = world.capacity();
-            newworld = Vec::from_raw_parts(ptr as *mut u8, len, cap);
-        }
-        /*     for i in 0..world.len() {
-          newworld.push(world[i] as u8);
-        } */
-        let size = width * height * length;
-        let mut data = vec![0; 4];
-        data[0] = (size >> 24) as u8;
-        data[1] = (size >> 16) as u8;
-        data[2] = (size >> 8) as u8;
-        data[3] = size as u8;
-        data.append(&mut newworld);
-        //let data = data.into_boxed_slice();
-        Some(Self {
-            data: Arc::new(RwLock::new(data.into_boxed_slice())),
+    /// Creates a world from its block array, without a length
+    /// prefix. `upper` holds the second ExtendedBlocks array.
+    pub fn from_data(data: Vec<u8>, upper: Option<Vec<u8>>, width: i16, height: i16, length: i16) -> anyhow::Result<Self> {
+        let (width, height, length) = (width.max(0) as usize, height.max(0) as usize, length.max(0) as usize);
+        let volume = width * height * length;
+        if data.len() != volume {
+            bail!("world is {}x{}x{} but has {} blocks", width, height, length, data.len());
+        }
+        if upper.as_ref().is_some_and(|u| u.len() != volume) {
+            bail!("world has a different number of upper block bits than blocks");
+        }
+        let data = BlockArray {
+            lower: data.into_boxed_slice(),
+            upper: upper.map(Vec::into_boxed_slice),
+        };
+        Ok(Self {
+            data: Arc::new(RwLock::new(data)),
+            dirty: Default::default(),
             width,
             height,
             length,
         })
     }
-
     pub fn new(generator: impl WorldGenerator, width: usize, height: usize, length: usize) -> Self {
-        let size = width * height * length;
-        let mut data = vec![0; size + 4].into_boxed_slice();
-        // Big-endian length of blocks array number
-        // TODO use bytes to simplify into .put_be_i32() or something
-        data[0] = (size >> 24) as u8;
-        data[1] = (size >> 16) as u8;
-        data[2] = (size >> 8) as u8;
-        data[3] = size as u8;
-        generator.generate(&mut data[4..], width, height, length);
+        let mut data = vec![0; width * height * length].into_boxed_slice();
+        generator.generate(&mut data, width, height, length);
         Self {
-            data: Arc::new(RwLock::new(data)),
+            data: Arc::new(RwLock::new(BlockArray::new(data))),
+            dirty: Default::default(),
             width,
             height,
             length,
         }
     }
 
-    pub fn pos_to_index(&self, x: usize, y: usize, z: usize) -> usize {
-        if x > self.width || y > self.height || z > self.length {
-            // TODO bad
-            return 0;
+    /// Whether `position` is inside the world.
+    pub fn contains(&self, position: BlockPosition) -> bool {
+        let BlockPosition { x, y, z } = position;
+        x >= 0 && y >= 0 && z >= 0
+            && (x as usize) < self.width
+            && (y as usize) < self.height
+            && (z as usize) < self.length
+    }
+
+    /// Index of a position in the block array, which is ordered
+    /// by Y, then Z, then X. `None` outside the world.
+    pub fn index_of(&self, position: BlockPosition) -> Option<usize> {
+        if !self.contains(position) {
+            return None;
         }
-        (z + y * self.length) * self.width + x + 4
+        let (x, y, z) = (position.x as usize, position.y as usize, position.z as usize);
+        Some((z + y * self.length) * self.width + x)
     }
 
-    // TODO position struct type stuff
-    pub fn get_block(&self, x: usize, y: usize, z: usize) -> u8 {
-        if x > self.width || y > self.height || z > self.length {
-            return 0;
+    /// Converts an index into the block array (as used
+    /// by `BulkBlockUpdate`) back into a position.
+    pub fn index_to_position(&self, index: usize) -> Option<BlockPosition> {
+        if index >= self.width * self.height * self.length {
+            return None;
         }
-        self.data.read().unwrap().get(self.pos_to_index(x, y, z)).copied().unwrap_or(0)
+        let x = index % self.width;
+        let z = (index / self.width) % self.length;
+        let y = index / (self.width * self.length);
+        Some(BlockPosition::new(x as i32, y as i32, z as i32))
+    }
+
+    /// The block at `position`, or `None` outside the world.
+    pub fn get(&self, position: BlockPosition) -> Option<BlockId> {
+        let index = self.index_of(position)?;
+        self.data.read().unwrap().get(index)
     }
 
-    pub fn set_block(&mut self, block: Block) {
-        let mut data = self.data.write().unwrap();
-        data[self.pos_to_index(
-            block.position.x as usize,
-            block.position.y as usize,
-            block.position.z as usize,
-        )] = block.id;
+    /// The block at `position`, treating everything
+    /// outside the world as air.
+    pub fn get_block(&self, position: BlockPosition) -> BlockId {
+        self.get(position).unwrap_or(0)
     }
 
-    // pub fn data(&self) -> &[u8] {
-    //     &self.data
-    // }
+    /// Locks the blocks for reading once, for reading
+    /// many without locking for each.
+    pub fn blocks(&self) -> BlockView<'_> {
+        BlockView { world: self, blocks: self.data.read().unwrap() }
+    }
+
+    /// Changes the block at `position`. Fails
+    /// if it is outside the world.
+    pub fn set(&mut self, position: BlockPosition, id: BlockId) -> anyhow::Result<()> {
+        let index = self.index_of(position).ok_or_else(|| anyhow!("{:?} is outside the world", position))?;
+        self.data.write().unwrap().set(index, id);
+        self.mark_dirty(position);
+        Ok(())
+    }
 
-    // pub fn data_mut(&mut self) -> &mut [u8] {
-    //     &mut self.data
-    // }
+    /// Marks the chunk holding `position` for remeshing, along with
+    /// any chunk it borders, whose faces against it may now show or hide.
+    fn mark_dirty(&self, position: BlockPosition) {
+        let chunk = position.to_chunk();
+        let mut dirty = self.dirty.lock().unwrap();
+        dirty.insert(chunk);
+        let sizes = [self.width, self.height, self.length];
+        let coordinates = [position.x as usize, position.y as usize, position.z as usize];
+        for axis in 0..3 {
+            let mut neighbour = [chunk.x, chunk.y, chunk.z];
+            match coordinates[axis] & 15 {
+                0 if coordinates[axis] > 0 => neighbour[axis] -= 1,
+                15 if coordinates[axis] + 1 < sizes[axis] => neighbour[axis] += 1,
+                _ => continue,
+            }
+            dirty.insert(ChunkPosition::new(neighbour[0], neighbour[1], neighbour[2]));
+        }
+    }
+
+    /// Takes the chunks changed since the last call, each once.
+    pub fn take_dirty_chunks(&self) -> Vec<ChunkPosition> {
+        self.dirty.lock().unwrap().drain().collect()
+    }
+
+    pub fn set_block(&mut self, block: Block) -> anyhow::Result<()> {
+        self.set(block.position, block.id)
+    }
+
+    /// Returns the Y of the highest opaque block in
+    /// a column, or `None` if there is none.
+    pub fn column_height(&self, x: i32, z: i32) -> Option<i32> {
+        if !self.contains(BlockPosition::new(x, 0, z)) {
+            return None;
+        }
+        (0..self.height as i32).rev()
+            .find(|&y| block_is_opaque(self.get_block(BlockPosition::new(x, y, z))))
+    }
 
     pub fn width(&self) -> usize {
         self.width
@@ -189,6 +354,12 @@ pub trait WorldGenerator {
     fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize);
 }
 
+impl<G: WorldGenerator + ?Sized> WorldGenerator for Box<G> {
+    fn generate(&self, data: &mut [u8], width: usize, height: usize, length: usize) {
+        (**self).generate(data, width, height, length)
+    }
+}
+
 pub struct FlatWorldGenerator {
     height: usize,
     below: u8,
@@ -228,3 +399,161 @@ impl WorldGenerator for FlatWorldGenerator {
         }
     }
 }
+
+/// The blocks of a `World`, held locked for reading.
+pub struct BlockView<'a> {
+    world: &'a World,
+    blocks: RwLockReadGuard<'a, BlockArray>,
+}
+impl BlockView<'_> {
+    /// The block at `position`, or `None` outside the world.
+    pub fn get(&self, position: BlockPosition) -> Option<BlockId> {
+        self.blocks.get(self.world.index_of(position)?)
+    }
+    /// The block at `position`, treating everything
+    /// outside the world as air.
+    pub fn get_block(&self, position: BlockPosition) -> BlockId {
+        self.get(position).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut chunks: Vec<ChunkPosition>) -> Vec<(usize, usize, usize)> {
+        chunks.sort_by_key(|c| (c.x, c.y, c.z));
+        chunks.into_iter().map(|c| (c.x, c.y, c.z)).collect()
+    }
+
+    #[test]
+    fn block_view_matches_get() {
+        let mut world = World::new(FlatWorldGenerator::new(2, 1, 2, 0), 16, 8, 16);
+        world.set(BlockPosition::new(3, 4, 5), 300).unwrap();
+        let blocks = world.blocks();
+        for position in [BlockPosition::new(3, 4, 5), BlockPosition::new(0, 1, 0), BlockPosition::new(-1, 0, 0), BlockPosition::new(0, 8, 0)] {
+            assert_eq!(blocks.get(position), world.get(position));
+        }
+        assert_eq!(blocks.get_block(BlockPosition::new(16, 0, 0)), 0);
+    }
+
+    #[test]
+    fn edits_on_chunk_borders_dirty_neighbours() {
+        let mut world = World::new(FlatWorldGenerator::new(2, 1, 2, 0), 48, 16, 48);
+        assert!(world.take_dirty_chunks().is_empty());
+        world.set(BlockPosition::new(20, 5, 20), 1).unwrap();
+        world.set(BlockPosition::new(21, 6, 22), 1).unwrap();
+        assert_eq!(sorted(world.take_dirty_chunks()), [(1, 0, 1)]);
+        assert!(world.take_dirty_chunks().is_empty());
+
+        world.set(BlockPosition::new(16, 0, 31), 1).unwrap();
+        assert_eq!(sorted(world.take_dirty_chunks()), [(0, 0, 1), (1, 0, 1), (1, 0, 2)]);
+        // No neighbours past the edges of the world.
+        world.set(BlockPosition::new(0, 15, 47), 1).unwrap();
+        assert_eq!(sorted(world.clone().take_dirty_chunks()), [(0, 0, 2)]);
+    }
+
+    fn world() -> World {
+        World::new(FlatWorldGenerator::new(2, 1, 2, 0), 4, 3, 5)
+    }
+
+    #[test]
+    fn bounds_exclude_the_far_edges() {
+        let world = world();
+        assert!(world.contains(BlockPosition::new(0, 0, 0)));
+        assert!(world.contains(BlockPosition::new(3, 2, 4)));
+        assert!(!world.contains(BlockPosition::new(4, 0, 0)));
+        assert!(!world.contains(BlockPosition::new(0, 3, 0)));
+        assert!(!world.contains(BlockPosition::new(0, 0, 5)));
+        assert!(!world.contains(BlockPosition::new(-1, 0, 0)));
+        assert_eq!(world.get(BlockPosition::new(4, 0, 0)), None);
+        assert_eq!(world.get_block(BlockPosition::new(0, -1, 0)), 0);
+    }
+
+    #[test]
+    fn generator_fills_layers() {
+        let world = world();
+        assert_eq!(world.get(BlockPosition::new(3, 0, 4)), Some(1));
+        assert_eq!(world.get(BlockPosition::new(0, 1, 0)), Some(2));
+        assert_eq!(world.get(BlockPosition::new(2, 2, 2)), Some(0));
+        assert_eq!(world.column_height(1, 1), Some(1));
+        assert_eq!(world.column_height(-1, 1), None);
+    }
+
+    #[test]
+    fn column_height_sees_through_glass() {
+        let mut world = world();
+        world.set(BlockPosition::new(1, 2, 1), 20).unwrap();
+        world.set(BlockPosition::new(2, 2, 2), 1).unwrap();
+        assert_eq!(world.column_height(1, 1), Some(1));
+        assert_eq!(world.column_height(2, 2), Some(2));
+    }
+
+    #[test]
+    fn set_then_get() {
+        let mut world = world();
+        let position = BlockPosition::new(3, 2, 4);
+        world.set(position, 45).unwrap();
+        assert_eq!(world.get(position), Some(45));
+        // Neighbours, including the first block, are untouched.
+        assert_eq!(world.get(BlockPosition::new(2, 2, 4)), Some(0));
+        assert_eq!(world.get(BlockPosition::new(0, 0, 0)), Some(1));
+    }
+
+    #[test]
+    fn set_outside_fails() {
+        let mut world = world();
+        assert!(world.set(BlockPosition::new(4, 0, 0), 1).is_err());
+        assert!(world.set(BlockPosition::new(0, -1, 0), 1).is_err());
+        assert!(world.set_block(Block::new(1, BlockPosition::new(0, 0, 5))).is_err());
+        assert_eq!(world.data.read().unwrap().lower.len(), 4 * 3 * 5);
+    }
+
+    #[test]
+    fn extended_ids_round_trip() {
+        let mut world = world();
+        let position = BlockPosition::new(1, 1, 1);
+        world.set(position, 700).unwrap();
+        assert_eq!(world.get(position), Some(700));
+        assert_eq!(world.get(BlockPosition::new(0, 1, 1)), Some(2));
+    }
+
+    #[test]
+    fn indices_match_positions() {
+        let world = world();
+        for index in 0..4 * 3 * 5 {
+            let position = world.index_to_position(index).unwrap();
+            assert_eq!(world.index_of(position), Some(index));
+        }
+        assert_eq!(world.index_to_position(4 * 3 * 5), None);
+        assert_eq!(world.index_of(BlockPosition::new(1, 0, 0)), Some(1));
+        assert_eq!(world.index_of(BlockPosition::new(0, 0, 1)), Some(4));
+        assert_eq!(world.index_of(BlockPosition::new(0, 1, 0)), Some(20));
+    }
+
+    /// Prepends the big-endian block count Classic sends level data with.
+    fn with_length_prefix(blocks: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(blocks.len() + 4);
+        data.extend_from_slice(&(blocks.len() as u32).to_be_bytes());
+        data.extend_from_slice(blocks);
+        data
+    }
+
+    #[test]
+    fn length_prefix_round_trip() {
+        let world = world();
+        let data = with_length_prefix(&world.data.read().unwrap().lower);
+        assert_eq!(&data[..4], &60u32.to_be_bytes());
+        let blocks = strip_length_prefix(&data).unwrap();
+        assert_eq!(blocks, &*world.data.read().unwrap().lower);
+        assert!(strip_length_prefix(&data[..10]).is_err());
+        assert!(strip_length_prefix(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn from_data_checks_size() {
+        assert!(World::from_data(vec![0; 60], None, 4, 3, 5).is_ok());
+        assert!(World::from_data(vec![0; 64], None, 4, 3, 5).is_err());
+        assert!(World::from_data(vec![0; 60], Some(vec![0; 59]), 4, 3, 5).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Randomness and noise matching Classic's, so generated
+//! maps come out the same for the same seed.
+
+/// `java.util.Random`, which Classic's generator draws from.
+pub struct JavaRandom {
+    seed: i64,
+}
+impl JavaRandom {
+    const MULTIPLIER: i64 = 0x5DEECE66D;
+    const MASK: i64 = (1 << 48) - 1;
+
+    pub fn new(seed: i64) -> Self {
+        Self { seed: (seed ^ Self::MULTIPLIER) & Self::MASK }
+    }
+    fn next(&mut self, bits: u32) -> i32 {
+        self.seed = (self.seed.wrapping_mul(Self::MULTIPLIER).wrapping_add(0xB)) & Self::MASK;
+        (self.seed >> (48 - bits)) as i32
+    }
+    /// A number from 0 up to, but not including, `n`.
+    pub fn next_int(&mut self, n: i32) -> i32 {
+        if n & -n == n {
+            return ((n as i64 * self.next(31) as i64) >> 31) as i32;
+        }
+        loop {
+            let bits = self.next(31);
+            let value = bits % n;
+            if bits.checked_sub(value).and_then(|b| b.checked_add(n - 1)).is_some() {
+                return value;
+            }
+        }
+    }
+    /// A number from `min` up to, but not including, `max`.
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        min + self.next_int(max - min)
+    }
+    /// A number from 0 up to, but not including, 1.
+    pub fn next_float(&mut self) -> f32 {
+        self.next(24) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Ken Perlin's improved noise, sampled on a plane.
+pub struct ImprovedNoise {
+    p: [u8; 512],
+}
+impl ImprovedNoise {
+    pub fn new(random: &mut JavaRandom) -> Self {
+        let mut p = [0; 512];
+        for (i, v) in p.iter_mut().take(256).enumerate() {
+            *v = i as u8;
+        }
+        for i in 0..256 {
+            let j = random.range(i as i32, 256) as usize;
+            p.swap(i, j);
+        }
+        for i in 0..256 {
+            p[i + 256] = p[i];
+        }
+        Self { p }
+    }
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let (x_floor, y_floor) = (x.floor(), y.floor());
+        let (xi, yi) = ((x_floor as i32 & 0xFF) as usize, (y_floor as i32 & 0xFF) as usize);
+        let (x, y) = (x - x_floor, y - y_floor);
+        let (u, v) = (fade(x), fade(y));
+        let p = &self.p;
+        let a = p[xi] as usize + yi;
+        let b = p[xi + 1] as usize + yi;
+        let c1 = lerp(u, grad(p[p[a] as usize], x, y), grad(p[p[b] as usize], x - 1., y));
+        let c2 = lerp(u, grad(p[p[a + 1] as usize], x, y - 1.), grad(p[p[b + 1] as usize], x - 1., y - 1.));
+        lerp(v, c1, c2)
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// Perlin's gradient function with Z fixed at 0.
+fn grad(hash: u8, x: f32, y: f32) -> f32 {
+    let h = hash & 0xF;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => 0.,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Several layers of noise, each twice as large and half as detailed.
+pub struct OctaveNoise {
+    octaves: Vec<ImprovedNoise>,
+}
+impl OctaveNoise {
+    pub fn new(random: &mut JavaRandom, octaves: usize) -> Self {
+        Self { octaves: (0..octaves).map(|_| ImprovedNoise::new(random)).collect() }
+    }
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let (mut amplitude, mut frequency, mut sum) = (1., 1., 0.);
+        for octave in &self.octaves {
+            sum += octave.sample(x * frequency, y * frequency) * amplitude;
+            amplitude *= 2.;
+            frequency *= 0.5;
+        }
+        sum
+    }
+}
+
+/// Octave noise sampled at a position offset by a second octave noise.
+pub struct CombinedNoise {
+    noise: OctaveNoise,
+    offset: OctaveNoise,
+}
+impl CombinedNoise {
+    pub fn new(random: &mut JavaRandom, octaves: usize, offset_octaves: usize) -> Self {
+        let noise = OctaveNoise::new(random, octaves);
+        let offset = OctaveNoise::new(random, offset_octaves);
+        Self { noise, offset }
+    }
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let offset = self.offset.sample(x, y);
+        self.noise.sample(x + offset, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_java_random() {
+        let mut random = JavaRandom::new(42);
+        assert_eq!(random.next(32), -1170105035);
+        assert_eq!(random.next(32), 234785527);
+    }
+
+    #[test]
+    fn next_int_stays_in_range() {
+        let mut random = JavaRandom::new(7);
+        for n in [1, 2, 3, 10, 64, 1000] {
+            for _ in 0..100 {
+                assert!((0..n).contains(&random.next_int(n)));
+            }
+        }
+        for _ in 0..100 {
+            assert!((0. ..1.).contains(&random.next_float()));
+        }
+    }
+
+    #[test]
+    fn noise_is_zero_on_lattice_points() {
+        let noise = ImprovedNoise::new(&mut JavaRandom::new(1));
+        assert_eq!(noise.sample(3., 5.), 0.);
+        assert_eq!(noise.sample(-2., 0.), 0.);
+        assert!(noise.sample(3.5, 5.5).abs() <= 2.);
+    }
+}
@@ -0,0 +1,121 @@
+//! Finding the block a ray hits, by stepping through the
+//! blocks it crosses in order (Amanatides and Woo's DDA).
+
+use glam::Vec3;
+
+use super::{block_collision, BlockCollision, BlockId, BlockPosition, Facing, World};
+
+/// A block found by `World::raycast`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RayHit {
+    pub position: BlockPosition,
+    /// The side of the block the ray entered through. The block
+    /// against it is at `position.offset(face)`.
+    pub face: Facing,
+    /// Distance along the ray to where it entered the block, in blocks.
+    pub distance: f32,
+}
+
+/// Whether the player can aim at a block: anything but air and liquids.
+pub fn targetable(id: BlockId) -> bool {
+    id != 0 && block_collision(id) != BlockCollision::Liquid
+}
+
+impl World {
+    /// Follows a ray from `origin`, in blocks, for up to `reach` blocks,
+    /// returning the first block for which `filter` is true. The block
+    /// holding `origin` is skipped, as it has no face to enter by.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, reach: f32, filter: impl Fn(BlockId) -> bool) -> Option<RayHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return None;
+        }
+        let start = origin.floor();
+        let mut block = [start.x as i32, start.y as i32, start.z as i32];
+        let mut step = [0; 3];
+        // Distance along the ray to the next boundary on each axis,
+        // and between boundaries on each axis.
+        let mut next = [f32::INFINITY; 3];
+        let mut delta = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            let d = direction[axis];
+            if d > 0. {
+                step[axis] = 1;
+                next[axis] = (start[axis] + 1. - origin[axis]) / d;
+            } else if d < 0. {
+                step[axis] = -1;
+                next[axis] = (origin[axis] - start[axis]) / -d;
+            } else {
+                continue;
+            }
+            delta[axis] = 1. / d.abs();
+        }
+        loop {
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            let distance = next[axis];
+            if distance > reach {
+                return None;
+            }
+            block[axis] += step[axis];
+            next[axis] += delta[axis];
+            let face = match (axis, step[axis] > 0) {
+                (0, true) => Facing::Right,
+                (0, false) => Facing::Left,
+                (1, true) => Facing::Bottom,
+                (1, false) => Facing::Top,
+                (_, true) => Facing::Front,
+                (_, false) => Facing::Back,
+            };
+            let position = BlockPosition::new(block[0], block[1], block[2]);
+            if self.get(position).is_some_and(&filter) {
+                return Some(RayHit { position, face, distance });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+    use crate::game::world::FlatWorldGenerator;
+
+    /// Stone up to Y 1 and grass at Y 2.
+    fn world() -> World {
+        World::new(FlatWorldGenerator::new(3, 1, 2, 0), 16, 8, 16)
+    }
+
+    #[test]
+    fn hits_the_top_of_the_ground() {
+        let hit = world().raycast(vec3(4.5, 6.5, 4.5), vec3(0., -1., 0.), 5., targetable).unwrap();
+        assert_eq!(hit.position, BlockPosition::new(4, 2, 4));
+        assert_eq!(hit.face, Facing::Top);
+        assert_eq!(hit.position.offset(hit.face), BlockPosition::new(4, 3, 4));
+        assert!((hit.distance - 3.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn stops_at_reach() {
+        let world = world();
+        assert!(world.raycast(vec3(4.5, 6.5, 4.5), vec3(0., -1., 0.), 3., targetable).is_none());
+        assert!(world.raycast(vec3(4.5, 6.5, 4.5), Vec3::ZERO, 5., targetable).is_none());
+    }
+
+    #[test]
+    fn filter_skips_blocks() {
+        let hit = world().raycast(vec3(4.5, 6.5, 4.5), vec3(0., -1., 0.), 5., |id| id == 1).unwrap();
+        assert_eq!(hit.position, BlockPosition::new(4, 1, 4));
+    }
+
+    #[test]
+    fn enters_walls_from_the_side() {
+        let mut world = world();
+        world.set(BlockPosition::new(2, 3, 5), 45).unwrap();
+        let hit = world.raycast(vec3(6.5, 3.5, 5.2), vec3(-1., 0., 0.1), 8., targetable).unwrap();
+        assert_eq!(hit.position, BlockPosition::new(2, 3, 5));
+        assert_eq!(hit.face, Facing::Left);
+        let hit = world.raycast(vec3(0.5, 3.5, 5.5), vec3(1., 0., 0.), 8., targetable).unwrap();
+        assert_eq!(hit.face, Facing::Right);
+    }
+}
@@ -0,0 +1,295 @@
+//! MCEdit schematics, for copying regions between maps. Schematics
+//! are written with Classic's block IDs, as Minecraft's have no match
+//! for several cloth colours, and both can be read back.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use nbt::{decode::TagDecodeError, encode::write_gzip_compound_tag, CompoundTag, CompoundTagError};
+use thiserror::Error;
+
+use super::{BlockId, BlockPosition, FlatWorldGenerator, World};
+
+/// Materials of schematics using Minecraft's block IDs.
+const ALPHA: &str = "Alpha";
+/// Materials of schematics using Classic's block IDs.
+const CLASSIC: &str = "Classic";
+/// Minecraft's wool, which Classic's cloth blocks are read from.
+const WOOL: u8 = 35;
+
+/// Error when loading or saving a schematic.
+#[derive(Debug, Error)]
+pub enum SchematicError {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid NBT: {0}")]
+    Nbt(#[from] TagDecodeError),
+    #[error("missing tag `{0}`")]
+    MissingTag(String),
+    #[error("tag `{0}` has the wrong type")]
+    WrongTagType(String),
+    #[error("unsupported materials {0:?}")]
+    UnsupportedMaterials(String),
+    #[error("invalid schematic: {0}")]
+    Invalid(String),
+    #[error("region is outside the world")]
+    OutsideWorld,
+}
+impl From<CompoundTagError<'_>> for SchematicError {
+    fn from(e: CompoundTagError) -> Self {
+        match e {
+            CompoundTagError::TagNotFound { name } => SchematicError::MissingTag(name.to_string()),
+            CompoundTagError::TagWrongType { name, .. } => SchematicError::WrongTagType(name.to_string()),
+        }
+    }
+}
+
+/// The Classic block for a Minecraft ID and data value. Blocks
+/// with no close match in Classic or CPE become stone.
+fn from_alpha(id: u8, data: u8) -> BlockId {
+    match (id, data) {
+        (0..=20, _) | (37..=43, _) | (45..=49, _) => id as BlockId,
+        (44, 3) => 50,
+        (44, _) => 44,
+        (WOOL, color) => match color & 0xF {
+            0 => 36,
+            1 => 22,
+            2 => 32,
+            3 => 27,
+            4 => 23,
+            5 => 24,
+            6 => 33,
+            7 => 34,
+            8 => 35,
+            9 => 28,
+            10 => 31,
+            11 => 29,
+            12 => 57,
+            13 => 25,
+            14 => 21,
+            _ => 34,
+        },
+        (24, _) => 52,
+        (51, _) => 54,
+        (78, _) | (80, _) => 53,
+        (79, _) => 60,
+        (87, _) => 62,
+        (98, 3) => 61,
+        (98, _) => 65,
+        (106, _) => 51,
+        (155, 2) => 63,
+        _ => 1,
+    }
+}
+
+/// A cuboid of blocks, stored in the same Y, Z, X order as worlds.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Schematic {
+    width: usize,
+    height: usize,
+    length: usize,
+    blocks: Vec<BlockId>,
+}
+impl Schematic {
+    /// Copies the blocks between two corners, in any order and both
+    /// included. The whole region has to be inside the world.
+    pub fn from_region(world: &World, a: BlockPosition, b: BlockPosition) -> Result<Self, SchematicError> {
+        let min = BlockPosition::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = BlockPosition::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        if !world.contains(min) || !world.contains(max) {
+            return Err(SchematicError::OutsideWorld);
+        }
+        let mut blocks = Vec::new();
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    blocks.push(world.get_block(BlockPosition::new(x, y, z)));
+                }
+            }
+        }
+        Ok(Self {
+            width: (max.x - min.x + 1) as usize,
+            height: (max.y - min.y + 1) as usize,
+            length: (max.z - min.z + 1) as usize,
+            blocks,
+        })
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn length(&self) -> usize {
+        self.length
+    }
+    /// Every block with its position when pasted with
+    /// its lowest corner at `origin`, air included.
+    pub fn blocks_at(&self, origin: BlockPosition) -> impl Iterator<Item = (BlockPosition, BlockId)> + '_ {
+        let (width, length) = (self.width, self.length);
+        self.blocks.iter().enumerate().map(move |(i, &block)| {
+            let (x, z, y) = (i % width, (i / width) % length, i / (width * length));
+            (BlockPosition::new(origin.x + x as i32, origin.y + y as i32, origin.z + z as i32), block)
+        })
+    }
+    /// Pastes the schematic with its lowest corner at `origin`. Parts
+    /// outside the world are cut off. Returns how many blocks were set.
+    pub fn paste(&self, world: &mut World, origin: BlockPosition) -> usize {
+        self.blocks_at(origin)
+            .filter(|&(position, block)| world.set(position, block).is_ok())
+            .count()
+    }
+    /// A world holding just the schematic.
+    pub fn to_world(&self) -> World {
+        // Air throughout, before pasting.
+        let mut world = World::new(FlatWorldGenerator::new(1, 0, 0, 0), self.width, self.height, self.length);
+        self.paste(&mut world, BlockPosition::new(0, 0, 0));
+        world
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SchematicError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+    /// Reads a schematic from its decompressed root tag, with
+    /// either Minecraft's or Classic's materials.
+    pub fn from_tag(root: &CompoundTag) -> Result<Self, SchematicError> {
+        let (width, height, length) = (root.get_i16("Width")?, root.get_i16("Height")?, root.get_i16("Length")?);
+        if width < 0 || height < 0 || length < 0 {
+            return Err(SchematicError::Invalid("negative size".to_string()));
+        }
+        let (width, height, length) = (width as usize, height as usize, length as usize);
+        let ids = root.get_i8_vec("Blocks")?;
+        if ids.len() != width * height * length {
+            return Err(SchematicError::Invalid(format!("expected {} blocks, found {}", width * height * length, ids.len())));
+        }
+        let blocks = match root.get_str("Materials")? {
+            CLASSIC => ids.iter().map(|&id| id as u8 as BlockId).collect(),
+            ALPHA => {
+                let data = root.get_i8_vec("Data").ok();
+                ids.iter().enumerate()
+                    .map(|(i, &id)| from_alpha(id as u8, data.and_then(|d| d.get(i)).map_or(0, |&d| d as u8)))
+                    .collect()
+            }
+            other => return Err(SchematicError::UnsupportedMaterials(other.to_string())),
+        };
+        Ok(Self { width, height, length, blocks })
+    }
+    /// Writes the schematic gzipped, with Classic's materials.
+    /// Custom blocks past 255 become stone.
+    pub fn write(&self, mut writer: impl Write) -> Result<(), SchematicError> {
+        let ids = self.blocks.iter().map(|&block| u8::try_from(block).unwrap_or(1) as i8).collect();
+        let mut root = CompoundTag::named("Schematic");
+        root.insert_i16("Width", self.width as i16);
+        root.insert_i16("Height", self.height as i16);
+        root.insert_i16("Length", self.length as i16);
+        root.insert_str("Materials", CLASSIC);
+        root.insert_i8_vec("Blocks", ids);
+        root.insert_i8_vec("Data", vec![0; self.blocks.len()]);
+        root.insert_compound_tag_vec("Entities", Vec::new());
+        root.insert_compound_tag_vec("TileEntities", Vec::new());
+        write_gzip_compound_tag(&mut writer, &root)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nbt::decode::read_gzip_compound_tag;
+
+    use super::*;
+
+    fn read(mut data: &[u8]) -> Result<Schematic, SchematicError> {
+        Schematic::from_tag(&read_gzip_compound_tag(&mut data)?)
+    }
+
+    fn world() -> World {
+        let mut world = World::new(FlatWorldGenerator::new(2, 1, 2, 0), 8, 8, 8);
+        world.set(BlockPosition::new(2, 2, 3), 21).unwrap();
+        world.set(BlockPosition::new(3, 2, 3), 50).unwrap();
+        world.set(BlockPosition::new(3, 3, 4), 45).unwrap();
+        world
+    }
+
+    #[test]
+    fn copies_a_region() {
+        let schematic = Schematic::from_region(&world(), BlockPosition::new(3, 3, 4), BlockPosition::new(2, 1, 3)).unwrap();
+        assert_eq!((schematic.width(), schematic.height(), schematic.length()), (2, 3, 2));
+        let blocks = schematic.blocks_at(BlockPosition::new(2, 1, 3)).collect::<Vec<_>>();
+        assert_eq!(blocks[0], (BlockPosition::new(2, 1, 3), 2));
+        assert!(blocks.contains(&(BlockPosition::new(2, 2, 3), 21)));
+        assert_eq!(*blocks.last().unwrap(), (BlockPosition::new(3, 3, 4), 45));
+        assert!(matches!(
+            Schematic::from_region(&world(), BlockPosition::new(0, 0, 0), BlockPosition::new(8, 0, 0)),
+            Err(SchematicError::OutsideWorld),
+        ));
+    }
+
+    #[test]
+    fn round_trips_every_block() {
+        let schematic = Schematic::from_region(&world(), BlockPosition::new(0, 0, 0), BlockPosition::new(7, 7, 7)).unwrap();
+        let mut data = Vec::new();
+        schematic.write(&mut data).unwrap();
+        assert_eq!(read(&data).unwrap(), schematic);
+
+        // Every Classic and CPE block, including cloth colours Minecraft merges.
+        let blocks = Schematic { width: 66, height: 1, length: 1, blocks: (0..66).collect() };
+        let mut data = Vec::new();
+        blocks.write(&mut data).unwrap();
+        assert_eq!(read(&data).unwrap(), blocks);
+    }
+
+    #[test]
+    fn reads_minecraft_materials() {
+        let mut root = CompoundTag::named("Schematic");
+        root.insert_i16("Width", 3);
+        root.insert_i16("Height", 1);
+        root.insert_i16("Length", 1);
+        root.insert_str("Materials", ALPHA);
+        root.insert_i8_vec("Blocks", vec![WOOL as i8, 44, 98]);
+        root.insert_i8_vec("Data", vec![14, 3, 3]);
+        let mut data = Vec::new();
+        write_gzip_compound_tag(&mut data, &root).unwrap();
+        assert_eq!(read(&data).unwrap().blocks, vec![21, 50, 61]);
+    }
+
+    #[test]
+    fn pastes_and_clips() {
+        let schematic = Schematic::from_region(&world(), BlockPosition::new(2, 2, 3), BlockPosition::new(3, 3, 4)).unwrap();
+        let mut target = World::new(FlatWorldGenerator::new(1, 3, 3, 0), 4, 4, 4);
+        assert_eq!(schematic.paste(&mut target, BlockPosition::new(0, 1, 0)), 8);
+        assert_eq!(target.get(BlockPosition::new(0, 1, 0)), Some(21));
+        assert_eq!(target.get(BlockPosition::new(1, 1, 0)), Some(50));
+        assert_eq!(target.get(BlockPosition::new(1, 2, 1)), Some(45));
+        // Only the lowest corner fits.
+        assert_eq!(schematic.paste(&mut target, BlockPosition::new(3, 3, 3)), 1);
+        assert_eq!(target.get(BlockPosition::new(3, 3, 3)), Some(21));
+
+        let world = schematic.to_world();
+        assert_eq!((world.width(), world.height(), world.length()), (2, 2, 2));
+        assert_eq!(world.get(BlockPosition::new(1, 0, 0)), Some(50));
+    }
+
+    #[test]
+    fn reads_classic_materials() {
+        let mut root = CompoundTag::named("Schematic");
+        root.insert_i16("Width", 2);
+        root.insert_i16("Height", 1);
+        root.insert_i16("Length", 1);
+        root.insert_str("Materials", CLASSIC);
+        root.insert_i8_vec("Blocks", vec![21, 50]);
+        let mut data = Vec::new();
+        write_gzip_compound_tag(&mut data, &root).unwrap();
+        let schematic = read(&data).unwrap();
+        assert_eq!(schematic.blocks, vec![21, 50]);
+
+        root.insert_str("Materials", "Pocket");
+        let mut data = Vec::new();
+        write_gzip_compound_tag(&mut data, &root).unwrap();
+        assert!(matches!(read(&data), Err(SchematicError::UnsupportedMaterials(_))));
+    }
+}
@@ -59,6 +59,15 @@ impl<T: Bufferable> VertexBuffer<T> {
         gl::VertexAttribPointer(index, T::size(), T::data_type(), gl::FALSE, 0, ptr::null());
         BufferBindHandle::new(index)
     }
+    /// Binds the buffer as a per-instance attribute,
+    /// advancing once per instance instead of per vertex.
+    /// # Safety
+    /// TODO
+    pub unsafe fn bind_instanced(&mut self, index: u32) -> BufferBindHandle {
+        let handle = self.bind(index);
+        gl::VertexAttribDivisor(index, 1);
+        handle
+    }
     /// # Safety
     /// TODO
     pub unsafe fn unbind(&mut self, index: u32) {
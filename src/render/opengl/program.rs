@@ -1,6 +1,6 @@
 // Copyright (c) Exopteron 2022
 use gl::types::GLenum;
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Vec2, Vec3, Vec4};
 
 use super::{get_uniform_location, texture::Texture2D};
 
@@ -41,11 +41,21 @@ impl Uniformable for f32 {
         gl::Uniform1f(uniform_location, *self);
     }
 }
+impl Uniformable for Vec2 {
+    unsafe fn bind_uniform(&self, uniform_location: i32) {
+        gl::Uniform2f(uniform_location, self.x, self.y);
+    }
+}
 impl Uniformable for Vec3 {
     unsafe fn bind_uniform(&self, uniform_location: i32) {
         gl::Uniform3f(uniform_location, self.x, self.y, self.z);
     }
 }
+impl Uniformable for Vec4 {
+    unsafe fn bind_uniform(&self, uniform_location: i32) {
+        gl::Uniform4f(uniform_location, self.x, self.y, self.z, self.w);
+    }
+}
 impl Uniformable for i32 {
     unsafe fn bind_uniform(&self, uniform_location: i32) {
         gl::Uniform1i(uniform_location, *self);
@@ -57,9 +57,11 @@ impl GameWindow {
             .take()
             .unwrap()
             .run(move |event, _, control_flow| {
-                let current_frame = Instant::now();
-                self.delta_time = current_frame - self.last_frame;
-                self.last_frame = current_frame;
+                if let Event::MainEventsCleared = event {
+                    let current_frame = Instant::now();
+                    self.delta_time = current_frame - self.last_frame;
+                    self.last_frame = current_frame;
+                }
                 *control_flow = ControlFlow::Poll;
                 // we want to pass this one up the chain
                 if let Event::WindowEvent {